@@ -7,25 +7,104 @@ pub trait Compare {
     fn closer_than(x: &Self::A, y: &Self::A) -> bool;
 }
 
+/// `Compare` for any `Ord` type, putting the greatest element on top.
+pub struct MaxByOrd<T> (rstd::marker::PhantomData<T>);
+
+impl<T: Ord> Compare for MaxByOrd<T> {
+    type A = T;
+    fn closer_than(x: &T, y: &T) -> bool { x > y }
+}
+
+/// `Compare` for any `Ord` type, putting the least element on top.
+pub struct MinByOrd<T> (rstd::marker::PhantomData<T>);
+
+impl<T: Ord> Compare for MinByOrd<T> {
+    type A = T;
+    fn closer_than(x: &T, y: &T) -> bool { x < y }
+}
+
+/// Which way a heap applies its `Compare`.
+pub trait Direction {
+    /// Whether `closer_than` is applied with its arguments swapped.
+    const REVERSED: bool;
+}
+
+/// Keep the ordering of the `Compare` as is.
+pub struct Natural;
+
+impl Direction for Natural {
+    const REVERSED: bool = false;
+}
+
+/// Flip the ordering of the `Compare`, e.g. to get a max-heap out of a min comparator.
+pub struct Reversed;
+
+impl Direction for Reversed {
+    const REVERSED: bool = true;
+}
+
 /// A generic heap, using `Compare` trait to customize the sorting type
-/// and using `StorageValue` as the storage.
-pub struct Heap<T, C, S> (rstd::marker::PhantomData<(T, C, S)>);
+/// and using `StorageValue` as the storage. `D` picks whether the comparator
+/// is used as is or flipped.
+pub struct Heap<T, C, S, D = Natural> (rstd::marker::PhantomData<(T, C, S, D)>);
+
+/// Iterator returned by `Heap::drain_sorted`, popping from an in-memory copy of the store.
+pub struct DrainSorted<T, C, S, D> {
+    store: Vec<T>,
+    _marker: rstd::marker::PhantomData<(C, S, D)>,
+}
 
-impl<T, C, S> Heap<T, C, S>
+impl<T, C, S, D> Iterator for DrainSorted<T, C, S, D>
     where T: Parameter,
           C: Compare<A=T>,
           S: StorageValue<Vec<T>, Query=Vec<T>>,
+          D: Direction,
 {
-    /// Push a value into heap and update storage.
-    pub fn push(item: T) {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        Heap::<T, C, S, D>::pop_from_store(&mut self.store)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.store.len(), Some(self.store.len()))
+    }
+}
+
+impl<T, C, S, D> Heap<T, C, S, D>
+    where T: Parameter,
+          C: Compare<A=T>,
+          S: StorageValue<Vec<T>, Query=Vec<T>>,
+          D: Direction,
+{
+    /// Push a value into heap and update storage. Returns the new length of the heap.
+    pub fn push(item: T) -> usize {
+        let mut store = S::get();
+        Self::push_into_store(&mut store, item);
+        let len = store.len();
+        S::put(store);
+        len
+    }
+
+    /// Like `push`, but hands `item` back instead of growing the heap past `max_len`.
+    pub fn try_push(item: T, max_len: usize) -> Result<(), T> {
         let mut store = S::get();
+        if store.len() >= max_len {
+            return Err(item);
+        }
         Self::push_into_store(&mut store, item);
         S::put(store);
+        Ok(())
     }
 
-    /// Push a vector into heap and update the storage.
+    /// Push a vector into heap and update the storage. An empty `items` leaves the
+    /// storage untouched.
     pub fn push_vec(items: Vec<T>) {
+        if items.is_empty() {
+            return;
+        }
         let mut store = S::get();
+        store.reserve(items.len());
         for item in items {
             Self::push_into_store(&mut store, item);
         }
@@ -40,6 +119,29 @@ impl<T, C, S> Heap<T, C, S>
         top
     }
 
+    /// Pop the top element only if `pred` holds for it. Storage is left untouched
+    /// when it doesn't, or when the heap is empty.
+    pub fn pop_if<F: Fn(&T) -> bool>(pred: F) -> Option<T> {
+        let mut store = S::get();
+        match store.first() {
+            Some(top) if pred(top) => {
+                let top = Self::pop_from_store(&mut store);
+                S::put(store);
+                top
+            }
+            _ => None,
+        }
+    }
+
+    /// Like `pop`, but also returns the length of the heap after popping.
+    pub fn pop_len() -> (Option<T>, usize) {
+        let mut store = S::get();
+        let top = Self::pop_from_store(&mut store);
+        let len = store.len();
+        S::put(store);
+        (top, len)
+    }
+
     /// Pop the top elements of heap which closer to the top of heap than
     /// `stake` and update the storage. More efficient than `pop` one by one.
     pub fn pop_vec(stake: &T) -> Vec<T> {
@@ -49,6 +151,181 @@ impl<T, C, S> Heap<T, C, S>
         vec
     }
 
+    /// `pop_vec`, also returning how many elements are left, without reading the
+    /// storage a second time.
+    pub fn pop_vec_with_remaining(stake: &T) -> (Vec<T>, usize) {
+        let mut store = S::get();
+        let vec = Self::pop_by_stake(&mut store, stake);
+        let remaining = store.len();
+        S::put(store);
+        (vec, remaining)
+    }
+
+    /// `pop_vec_with_remaining`, but stops early once `afford` refuses a pop. `afford`
+    /// is given the length of the heap before each pop.
+    pub fn pop_vec_within<F: FnMut(usize) -> bool>(stake: &T, mut afford: F) -> (Vec<T>, usize) {
+        let mut store = S::get();
+        let mut vec = Vec::new();
+        while store.first().map_or(false, |top| Self::closer_than(top, stake)) && afford(store.len()) {
+            match Self::pop_from_store(&mut store) {
+                None => break,
+                Some(top) => vec.push(top),
+            }
+        }
+        let remaining = store.len();
+        S::put(store);
+        (vec, remaining)
+    }
+
+    /// Pop the top elements of heap as long as `pred` holds for the top and
+    /// update the storage.
+    pub fn pop_while<F: Fn(&T) -> bool>(pred: F) -> Vec<T> {
+        let mut store = S::get();
+        let vec = Self::pop_from_store_while(&mut store, pred);
+        S::put(store);
+        vec
+    }
+
+    /// Replace the storage with `items` in any order, then restore the heap
+    /// invariant bottom-up. Unlike `push_vec` it does not assume an existing heap.
+    pub fn from_vec(items: Vec<T>) {
+        let mut store = items;
+        Self::heapify(&mut store);
+        S::put(store);
+    }
+
+    /// Keep only the elements for which `keep` returns true, re-heapify once
+    /// and update the storage.
+    pub fn retain<F: Fn(&T) -> bool>(keep: F) {
+        let mut store = S::get();
+        store.retain(|item| keep(item));
+        Self::heapify(&mut store);
+        S::put(store);
+    }
+
+    /// The `k` elements closest to the top of heap, in order. Works on a copy
+    /// of the store and leaves the storage untouched.
+    pub fn top_k(k: usize) -> Vec<T> {
+        let mut store = S::get();
+        let mut vec = Vec::with_capacity(k.min(store.len()));
+        while vec.len() < k {
+            match Self::pop_from_store(&mut store) {
+                None => break,
+                Some(top) => vec.push(top),
+            }
+        }
+        vec
+    }
+
+    /// Every element of the heap, in order. Leaves the storage untouched.
+    pub fn into_sorted_vec() -> Vec<T> {
+        Self::top_k(usize::max_value())
+    }
+
+    /// Empty the heap in storage right away and return its elements lazily, closest
+    /// to the top first. Elements not taken from the iterator are dropped.
+    pub fn drain_sorted() -> DrainSorted<T, C, S, D> {
+        let store = S::get();
+        S::kill();
+        DrainSorted { store, _marker: rstd::marker::PhantomData }
+    }
+
+    /// The backing store as is, in heap order. Together with `peek` and `len`
+    /// this is the supported way to inspect a heap; read these instead of the
+    /// storage item.
+    pub fn to_vec() -> Vec<T> {
+        S::get()
+    }
+
+    /// A copy of the heap for exporting state or saving a test fixture, see `restore`.
+    pub fn snapshot() -> Vec<T> {
+        Self::to_vec()
+    }
+
+    /// Load a heap saved by `snapshot`. Input that is not in heap order is
+    /// heapified instead of being stored as is.
+    pub fn restore(items: Vec<T>) {
+        let mut store = items;
+        if !Self::is_heap(&store) {
+            Self::heapify(&mut store);
+        }
+        S::put(store);
+    }
+
+    /// Restore the heap order of whatever is in storage, e.g. after the store was
+    /// written to directly. Returns whether anything had to be fixed; a valid heap
+    /// is left as is and not written back.
+    pub fn repair() -> bool {
+        let mut store = S::get();
+        if Self::is_heap(&store) {
+            return false;
+        }
+        Self::heapify(&mut store);
+        S::put(store);
+        true
+    }
+
+    /// The top element of heap, if any, without removing it.
+    pub fn peek() -> Option<T> {
+        S::get().into_iter().next()
+    }
+
+    /// Number of elements in the heap.
+    pub fn len() -> usize {
+        S::get().len()
+    }
+
+    /// The element farthest from the top of heap, e.g. the one to evict from a full
+    /// bounded heap. Only the leaves can hold it, so only the second half is scanned.
+    pub fn farthest() -> Option<T> {
+        let store = S::get();
+        let first_leaf = store.len() / 2;
+        let mut leaves = store.into_iter().skip(first_leaf);
+        let first = leaves.next()?;
+        Some(leaves.fold(first, |farthest, item| {
+            if Self::closer_than(&farthest, &item) { item } else { farthest }
+        }))
+    }
+
+    /// Index in the backing store of the first element matching `pred`. A linear
+    /// scan, meant for locating an element before updating it in place.
+    pub fn position<F: Fn(&T) -> bool>(pred: F) -> Option<usize> {
+        S::get().iter().position(|item| pred(item))
+    }
+
+    /// Restore the heap property around `index` after the element there was changed
+    /// in place, without re-heapifying everything. Out of range indexes are ignored.
+    pub fn sift_at(index: usize) {
+        let mut store = S::get();
+        if index >= store.len() {
+            return;
+        }
+        Self::shift_up(&mut store, index);
+        Self::shift_down(&mut store, index);
+        S::put(store);
+    }
+
+    fn is_heap(store: &[T]) -> bool {
+        (1..store.len()).all(|idx| match Self::parent_idx(idx) {
+            Some(par) => !Self::closer_than(&store[idx], &store[par]),
+            None => true,
+        })
+    }
+
+    fn heapify(store: &mut [T]) {
+        for idx in (0..store.len() / 2).rev() {
+            Self::shift_down(store, idx);
+        }
+    }
+
+    fn closer_than(x: &T, y: &T) -> bool {
+        if D::REVERSED {
+            C::closer_than(y, x)
+        } else {
+            C::closer_than(x, y)
+        }
+    }
+
     fn push_into_store(store: &mut Vec<T>, item: T) {
         store.push(item);
         let last = store.len() - 1;
@@ -56,40 +333,35 @@ impl<T, C, S> Heap<T, C, S>
     }
 
     fn pop_by_stake(store: &mut Vec<T>, stack: &T) -> Vec<T> {
+        Self::pop_from_store_while(store, |top| Self::closer_than(top, stack))
+    }
+
+    fn pop_from_store_while<F: Fn(&T) -> bool>(store: &mut Vec<T>, pred: F) -> Vec<T> {
         let mut vec = Vec::new();
-        let peek_top = store.get(0);
-        match peek_top {
-            None => vec,
-            Some(peek_top) => {
-                if C::closer_than(peek_top, stack) {
-                    let top = Self::pop_from_store(store);
-                    match top {
-                        None => vec,
-                        Some(top) => {
-                            vec.push(top);
-                            vec.append(&mut Self::pop_by_stake(store, stack));
-                            vec
-                        }
-                    }
-                } else {
-                    vec
-                }
+        while store.first().map_or(false, |top| pred(top)) {
+            match Self::pop_from_store(store) {
+                None => break,
+                Some(top) => vec.push(top),
             }
         }
+        vec
     }
 
+    // Takes the last element before touching the top, so no index is derived from a
+    // length that could be off and a store that is not a valid heap cannot panic.
+    // Such a store is only noticed when a child of the top is closer than the top itself.
     fn pop_from_store(store: &mut Vec<T>) -> Option<T> {
-        match store.len() {
-            0 => None,
-            1 => store.pop(),
-            _ => {
-                let last = store.len() - 1;
-                store.swap(0, last);
-                let top = store.pop();
-                Self::shift_down(store, 0);
-                top
+        if let Some((first, rest)) = store.split_first() {
+            if rest.iter().take(2).any(|child| Self::closer_than(child, first)) {
+                runtime_io::print("pop_from_store(): The store is not a valid heap, popping anyway");
             }
         }
+        let mut top = store.pop()?;
+        if let Some(first) = store.first_mut() {
+            rstd::mem::swap(first, &mut top);
+            Self::shift_down(store, 0);
+        }
+        Some(top)
     }
 
     fn parent_idx(child: usize) -> Option<usize> {
@@ -128,7 +400,7 @@ impl<T, C, S> Heap<T, C, S>
         match Self::parent_idx(idx) {
             None => {}
             Some(par) => {
-                if C::closer_than(&store[idx], &store[par]) {
+                if Self::closer_than(&store[idx], &store[par]) {
                     store.swap(idx, par);
                     Self::shift_up(store, par);
                 }
@@ -142,19 +414,19 @@ impl<T, C, S> Heap<T, C, S>
             Some(left) => {
                 match Self::right_idx(store, idx) {
                     None => {
-                        if C::closer_than(&store[left], &store[idx]) {
+                        if Self::closer_than(&store[left], &store[idx]) {
                             store.swap(idx, left);
                             Self::shift_down(store, left);
                         }
                     }
                     Some(right) => {
                         let closer =
-                            if C::closer_than(&store[left], &store[right]) {
+                            if Self::closer_than(&store[left], &store[right]) {
                                 left
                             } else {
                                 right
                             };
-                        if C::closer_than(&store[closer], &store[idx]) {
+                        if Self::closer_than(&store[closer], &store[idx]) {
                             store.swap(idx, closer);
                             Self::shift_down(store, closer);
                         }
@@ -165,6 +437,156 @@ impl<T, C, S> Heap<T, C, S>
     }
 }
 
+/// A double-ended heap over the same `StorageValue<Vec<T>>` storage as `Heap`.
+/// The "min" end is the element a `Heap` with the same `Compare` would pop
+/// first, the "max" end the one it would pop last; both are O(1) to peek and
+/// O(log n) to pop. Elements on even levels are closer to the top than all of
+/// their descendants, elements on odd levels are farther than all of theirs.
+pub struct MinMaxHeap<T, C, S> (rstd::marker::PhantomData<(T, C, S)>);
+
+impl<T, C, S> MinMaxHeap<T, C, S>
+    where T: Parameter,
+          C: Compare<A=T>,
+          S: StorageValue<Vec<T>, Query=Vec<T>>,
+{
+    /// Push a value into heap and update storage.
+    pub fn push(item: T) {
+        let mut store = S::get();
+        store.push(item);
+        let last = store.len() - 1;
+        Self::bubble_up(&mut store, last);
+        S::put(store);
+    }
+
+    /// The element closest to the top, if any.
+    pub fn peek_min() -> Option<T> {
+        S::get().into_iter().next()
+    }
+
+    /// The element farthest from the top, if any.
+    pub fn peek_max() -> Option<T> {
+        let store = S::get();
+        Self::max_idx(&store).map(|idx| store[idx].clone())
+    }
+
+    /// Pop the element closest to the top and update the storage.
+    pub fn pop_min() -> Option<T> {
+        let mut store = S::get();
+        let min = Self::remove_at(&mut store, 0);
+        S::put(store);
+        min
+    }
+
+    /// Pop the element farthest from the top and update the storage.
+    pub fn pop_max() -> Option<T> {
+        let mut store = S::get();
+        let max = match Self::max_idx(&store) {
+            None => None,
+            Some(idx) => Self::remove_at(&mut store, idx),
+        };
+        S::put(store);
+        max
+    }
+
+    fn max_idx(store: &[T]) -> Option<usize> {
+        match store.len() {
+            0 => None,
+            1 => Some(0),
+            2 => Some(1),
+            _ => if C::closer_than(&store[1], &store[2]) { Some(2) } else { Some(1) },
+        }
+    }
+
+    fn remove_at(store: &mut Vec<T>, idx: usize) -> Option<T> {
+        if idx >= store.len() {
+            return None;
+        }
+        let last = store.len() - 1;
+        store.swap(idx, last);
+        let item = store.pop();
+        if idx < store.len() {
+            Self::trickle_down(store, idx);
+        }
+        item
+    }
+
+    fn is_min_level(idx: usize) -> bool {
+        let mut level = 0;
+        let mut n = idx + 1;
+        while n > 1 {
+            n /= 2;
+            level += 1;
+        }
+        level % 2 == 0
+    }
+
+    fn parent(idx: usize) -> Option<usize> {
+        if idx == 0 { None } else { Some((idx - 1) / 2) }
+    }
+
+    fn grandparent(idx: usize) -> Option<usize> {
+        Self::parent(idx).and_then(Self::parent)
+    }
+
+    // Whether `x` belongs closer to the root than `y` on the given kind of level.
+    fn outranks(min_level: bool, x: &T, y: &T) -> bool {
+        if min_level { C::closer_than(x, y) } else { C::closer_than(y, x) }
+    }
+
+    fn bubble_up(store: &mut [T], idx: usize) {
+        let min_level = Self::is_min_level(idx);
+        match Self::parent(idx) {
+            None => {}
+            Some(par) => {
+                if Self::outranks(!min_level, &store[idx], &store[par]) {
+                    store.swap(idx, par);
+                    Self::bubble_up_grandparents(store, par, !min_level);
+                } else {
+                    Self::bubble_up_grandparents(store, idx, min_level);
+                }
+            }
+        }
+    }
+
+    fn bubble_up_grandparents(store: &mut [T], idx: usize, min_level: bool) {
+        if let Some(grand) = Self::grandparent(idx) {
+            if Self::outranks(min_level, &store[idx], &store[grand]) {
+                store.swap(idx, grand);
+                Self::bubble_up_grandparents(store, grand, min_level);
+            }
+        }
+    }
+
+    fn trickle_down(store: &mut [T], idx: usize) {
+        let min_level = Self::is_min_level(idx);
+        let first_child = idx * 2 + 1;
+        if first_child >= store.len() {
+            return;
+        }
+        // The best of the children and grandchildren for this kind of level.
+        let descendants = [first_child, first_child + 1,
+            first_child * 2 + 1, first_child * 2 + 2, first_child * 2 + 3, first_child * 2 + 4];
+        let mut best = first_child;
+        for &candidate in descendants.iter().skip(1) {
+            if candidate < store.len() && Self::outranks(min_level, &store[candidate], &store[best]) {
+                best = candidate;
+            }
+        }
+
+        if !Self::outranks(min_level, &store[best], &store[idx]) {
+            return;
+        }
+        store.swap(idx, best);
+        if best > first_child + 1 {
+            let par = (best - 1) / 2;
+            if Self::outranks(!min_level, &store[best], &store[par]) {
+                store.swap(best, par);
+            }
+            Self::trickle_down(store, best);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -239,6 +661,12 @@ mod tests {
     }
 
     type MaxHeap = Heap<i32, TestCompare, HeapStore>;
+    type MinHeap = Heap<i32, TestCompare, HeapStore, Reversed>;
+
+    // Whether no element is closer to the top than its parent.
+    fn is_max_heap(store: &[i32]) -> bool {
+        (1..store.len()).all(|idx| store[(idx - 1) / 2] >= store[idx])
+    }
 
     #[test]
     fn it_works_for_default_value() {
@@ -378,4 +806,395 @@ mod tests {
             assert_eq!(MaxHeap::pop_vec(&0), [0; 0].to_vec());
         });
     }
+
+    #[test]
+    fn direction_test() {
+        with_externalities(&mut new_test_ext(), || {
+            <HeapStore>::put([0; 0].to_vec());
+            MaxHeap::push_vec([30, 10, 50, 20, 40].to_vec());
+            assert_eq!(MaxHeap::pop(), Some(50));
+            assert_eq!(MaxHeap::pop(), Some(40));
+
+            <HeapStore>::put([0; 0].to_vec());
+            MinHeap::push_vec([30, 10, 50, 20, 40].to_vec());
+            assert_eq!(MinHeap::pop(), Some(10));
+            assert_eq!(MinHeap::pop(), Some(20));
+            assert_eq!(MinHeap::pop_vec(&40), [30].to_vec());
+            assert_eq!(MinHeap::pop(), Some(40));
+            assert_eq!(MinHeap::pop(), Some(50));
+            assert_eq!(MinHeap::pop(), None);
+        });
+    }
+
+    #[test]
+    fn from_vec_test() {
+        with_externalities(&mut new_test_ext(), || {
+            <HeapStore>::put([5, 6, 7].to_vec());
+            MaxHeap::from_vec([0; 0].to_vec());
+            assert_eq!(TemplateModule::heap_store(), [0; 0].to_vec());
+
+            let items = [3, 9, 1, 7, 5, 8, 2, 6, 4, 10].to_vec();
+            assert!(!is_max_heap(&items));
+            MaxHeap::from_vec(items);
+            let store = TemplateModule::heap_store();
+            assert!(is_max_heap(&store));
+            assert_eq!(store.len(), 10);
+            assert_eq!(MaxHeap::pop_vec(&0), [10, 9, 8, 7, 6, 5, 4, 3, 2, 1].to_vec());
+        });
+    }
+
+    #[test]
+    fn retain_test() {
+        with_externalities(&mut new_test_ext(), || {
+            <HeapStore>::put([0; 0].to_vec());
+            MaxHeap::push_vec([1, 2, 3, 4, 5, 6, 7, 8, 9, 10].to_vec());
+            MaxHeap::retain(|x| x % 2 == 1);
+            let store = TemplateModule::heap_store();
+            assert!(is_max_heap(&store));
+            assert_eq!(store.len(), 5);
+            assert_eq!(MaxHeap::pop_vec(&0), [9, 7, 5, 3, 1].to_vec());
+        });
+    }
+
+    #[test]
+    fn returned_len_test() {
+        with_externalities(&mut new_test_ext(), || {
+            <HeapStore>::put([0; 0].to_vec());
+            assert_eq!(MaxHeap::push(10), 1);
+            assert_eq!(MaxHeap::push(30), 2);
+            assert_eq!(MaxHeap::push(20), 3);
+            assert_eq!(MaxHeap::pop_len(), (Some(30), 2));
+            assert_eq!(MaxHeap::pop_len(), (Some(20), 1));
+            assert_eq!(MaxHeap::pop_len(), (Some(10), 0));
+            assert_eq!(MaxHeap::pop_len(), (None, 0));
+        });
+    }
+
+    #[test]
+    fn bulk_push_vec_test() {
+        with_externalities(&mut new_test_ext(), || {
+            <HeapStore>::put([0; 0].to_vec());
+            MaxHeap::push_vec((0..500).map(|x| (x * 7919) % 1000).collect());
+            MaxHeap::push_vec((0..500).map(|x| (x * 7919) % 1000 + 1000).collect());
+            let store = TemplateModule::heap_store();
+            assert_eq!(store.len(), 1000);
+            assert!(is_max_heap(&store));
+        });
+    }
+
+    #[test]
+    fn pop_while_test() {
+        with_externalities(&mut new_test_ext(), || {
+            <HeapStore>::put([0; 0].to_vec());
+            assert_eq!(MaxHeap::pop_while(|_| true), [0; 0].to_vec());
+
+            MaxHeap::push_vec([10, 20, 30, 40, 50, 60].to_vec());
+            assert_eq!(MaxHeap::pop_while(|x| x % 20 != 0), [0; 0].to_vec());
+            assert_eq!(MaxHeap::pop_while(|x| x % 20 == 0), [60].to_vec());
+            assert_eq!(MaxHeap::pop_while(|x| *x > 25 && *x < 55), [50, 40, 30].to_vec());
+            assert_eq!(TemplateModule::heap_store(), [20, 10].to_vec());
+            assert_eq!(MaxHeap::pop_while(|_| true), [20, 10].to_vec());
+            assert_eq!(TemplateModule::heap_store(), [0; 0].to_vec());
+        });
+    }
+
+    #[test]
+    fn top_k_test() {
+        with_externalities(&mut new_test_ext(), || {
+            <HeapStore>::put([0; 0].to_vec());
+            assert_eq!(MaxHeap::top_k(3), [0; 0].to_vec());
+
+            MaxHeap::push_vec([40, 10, 60, 30, 50, 20].to_vec());
+            let store = TemplateModule::heap_store();
+            let sorted = MaxHeap::into_sorted_vec();
+            assert_eq!(sorted, [60, 50, 40, 30, 20, 10].to_vec());
+            assert_eq!(MaxHeap::top_k(3), sorted[..3].to_vec());
+            assert_eq!(MaxHeap::top_k(0), [0; 0].to_vec());
+            assert_eq!(MaxHeap::top_k(10), sorted);
+            assert_eq!(TemplateModule::heap_store(), store);
+        });
+    }
+
+    #[test]
+    fn by_ord_compare_test() {
+        type OrdMinHeap = Heap<i32, MinByOrd<i32>, HeapStore>;
+        type OrdMaxHeap = Heap<i32, MaxByOrd<i32>, HeapStore>;
+        with_externalities(&mut new_test_ext(), || {
+            <HeapStore>::put([0; 0].to_vec());
+            OrdMinHeap::push_vec([30, 10, 50, 20, 40].to_vec());
+            assert_eq!(TemplateModule::heap_store()[0], 10);
+            assert_eq!(OrdMinHeap::pop_vec(&35), [10, 20, 30].to_vec());
+            assert_eq!(OrdMinHeap::into_sorted_vec(), [40, 50].to_vec());
+
+            <HeapStore>::put([0; 0].to_vec());
+            OrdMaxHeap::push_vec([30, 10, 50, 20, 40].to_vec());
+            assert_eq!(OrdMaxHeap::into_sorted_vec(), MaxHeap::into_sorted_vec());
+        });
+    }
+
+    type OrdMinMaxHeap = MinMaxHeap<i32, MinByOrd<i32>, HeapStore>;
+
+    // Whether every element is within the bounds set by its ancestors on min and max levels.
+    fn is_min_max_heap(store: &[i32]) -> bool {
+        (1..store.len()).all(|idx| {
+            let mut ancestor = idx;
+            while ancestor > 0 {
+                ancestor = (ancestor - 1) / 2;
+                let ok = if OrdMinMaxHeap::is_min_level(ancestor) {
+                    store[ancestor] <= store[idx]
+                } else {
+                    store[ancestor] >= store[idx]
+                };
+                if !ok {
+                    return false;
+                }
+            }
+            true
+        })
+    }
+
+    #[test]
+    fn min_max_heap_test() {
+        with_externalities(&mut new_test_ext(), || {
+            <HeapStore>::put([0; 0].to_vec());
+            assert_eq!(OrdMinMaxHeap::peek_min(), None);
+            assert_eq!(OrdMinMaxHeap::peek_max(), None);
+            assert_eq!(OrdMinMaxHeap::pop_max(), None);
+
+            let mut expected: Vec<i32> = (0..60).map(|x| (x * 37) % 101).collect();
+            for item in expected.iter() {
+                OrdMinMaxHeap::push(*item);
+                assert!(is_min_max_heap(&TemplateModule::heap_store()));
+            }
+            expected.sort();
+
+            for round in 0..expected.len() {
+                assert_eq!(OrdMinMaxHeap::peek_min(), expected.first().cloned());
+                assert_eq!(OrdMinMaxHeap::peek_max(), expected.last().cloned());
+                if round % 3 == 0 {
+                    assert_eq!(OrdMinMaxHeap::pop_min(), Some(expected.remove(0)));
+                } else {
+                    assert_eq!(OrdMinMaxHeap::pop_max(), expected.pop());
+                }
+                assert!(is_min_max_heap(&TemplateModule::heap_store()));
+            }
+            assert_eq!(OrdMinMaxHeap::pop_min(), None);
+            assert_eq!(TemplateModule::heap_store(), [0; 0].to_vec());
+        });
+    }
+
+    #[test]
+    fn accessors_test() {
+        with_externalities(&mut new_test_ext(), || {
+            <HeapStore>::put([0; 0].to_vec());
+            assert_eq!(MaxHeap::peek(), None);
+            assert_eq!(MaxHeap::len(), 0);
+
+            MaxHeap::push_vec([30, 10, 50, 20, 40].to_vec());
+            assert_eq!(MaxHeap::to_vec(), TemplateModule::heap_store());
+            assert_eq!(MaxHeap::peek(), Some(50));
+            assert_eq!(MaxHeap::len(), 5);
+        });
+    }
+
+    #[test]
+    fn pop_inconsistent_store_test() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(MaxHeap::pop(), None);
+            assert_eq!(MaxHeap::pop_len(), (None, 0));
+            assert_eq!(MaxHeap::pop_vec(&i32::min_value()), vec![]);
+
+            // Not a heap at all, as if written by something other than `Heap`.
+            <HeapStore>::put(vec![1, 5, 2, 9]);
+            let mut popped = Vec::new();
+            while let Some(top) = MaxHeap::pop() {
+                popped.push(top);
+            }
+            popped.sort();
+            assert_eq!(popped, vec![1, 2, 5, 9]);
+            assert_eq!(MaxHeap::pop(), None);
+        });
+    }
+
+    #[test]
+    fn snapshot_restore_test() {
+        with_externalities(&mut new_test_ext(), || {
+            MaxHeap::restore(vec![9, 7, 8, 1, 5, 2]);
+            assert_eq!(MaxHeap::snapshot(), vec![9, 7, 8, 1, 5, 2]);
+            assert_eq!(MaxHeap::into_sorted_vec(), vec![9, 8, 7, 5, 2, 1]);
+
+            let saved = MaxHeap::snapshot();
+            assert_eq!(MaxHeap::pop(), Some(9));
+            MaxHeap::restore(saved);
+            assert_eq!(MaxHeap::pop(), Some(9));
+            assert_eq!(MaxHeap::pop(), Some(8));
+
+            MaxHeap::restore(vec![1, 2, 3, 4]);
+            assert!(is_max_heap(&MaxHeap::snapshot()));
+            assert_eq!(MaxHeap::pop(), Some(4));
+        });
+    }
+
+    #[test]
+    fn position_test() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(MaxHeap::position(|x| *x == 1), None);
+
+            MaxHeap::restore(vec![9, 7, 8, 1, 5, 2]);
+            assert_eq!(MaxHeap::position(|x| *x == 9), Some(0));
+            assert_eq!(MaxHeap::position(|x| *x == 5), Some(4));
+            assert_eq!(MaxHeap::position(|x| *x < 3), Some(3));
+            assert_eq!(MaxHeap::position(|x| *x == 4), None);
+        });
+    }
+
+    #[test]
+    fn sift_at_test() {
+        with_externalities(&mut new_test_ext(), || {
+            MaxHeap::restore(vec![9, 7, 8, 1, 5, 2]);
+
+            // Raise a leaf above the root.
+            <HeapStore>::put(vec![9, 7, 8, 1, 10, 2]);
+            MaxHeap::sift_at(4);
+            assert!(is_max_heap(&MaxHeap::snapshot()));
+            assert_eq!(MaxHeap::peek(), Some(10));
+
+            // Lower the root below its children.
+            let mut store = MaxHeap::snapshot();
+            store[0] = 0;
+            <HeapStore>::put(store);
+            MaxHeap::sift_at(0);
+            assert!(is_max_heap(&MaxHeap::snapshot()));
+            assert_eq!(MaxHeap::into_sorted_vec(), vec![9, 8, 7, 2, 1, 0]);
+
+            MaxHeap::sift_at(100);
+            assert_eq!(MaxHeap::len(), 6);
+        });
+    }
+
+    #[test]
+    fn farthest_test() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(MaxHeap::farthest(), None);
+
+            MaxHeap::push(3);
+            assert_eq!(MaxHeap::farthest(), Some(3));
+
+            let items = vec![50, 3, 27, 8, 41, 19, 1, 33, 12, 6, 44];
+            for count in 2..=items.len() {
+                MaxHeap::from_vec(items[..count].to_vec());
+                assert_eq!(MaxHeap::farthest(), MaxHeap::snapshot().into_iter().min());
+                MinHeap::from_vec(items[..count].to_vec());
+                assert_eq!(MinHeap::farthest(), MinHeap::snapshot().into_iter().max());
+            }
+        });
+    }
+
+    #[test]
+    fn drain_sorted_test() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(MaxHeap::drain_sorted().next(), None);
+
+            MaxHeap::push_vec(vec![4, 9, 1, 7, 3]);
+            let mut drain = MaxHeap::drain_sorted();
+            assert_eq!(MaxHeap::len(), 0);
+            assert_eq!(drain.size_hint(), (5, Some(5)));
+            assert_eq!(drain.by_ref().take(2).collect::<Vec<_>>(), vec![9, 7]);
+            assert_eq!(drain.collect::<Vec<_>>(), vec![4, 3, 1]);
+
+            MinHeap::push_vec(vec![4, 9, 1]);
+            assert_eq!(MinHeap::drain_sorted().collect::<Vec<_>>(), vec![1, 4, 9]);
+            assert!(MinHeap::snapshot().is_empty());
+        });
+    }
+
+    #[test]
+    fn try_push_test() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(MaxHeap::try_push(5, 0), Err(5));
+            assert_eq!(MaxHeap::len(), 0);
+
+            assert_eq!(MaxHeap::try_push(5, 2), Ok(()));
+            assert_eq!(MaxHeap::try_push(9, 2), Ok(()));
+            assert_eq!(MaxHeap::try_push(7, 2), Err(7));
+            assert_eq!(MaxHeap::snapshot(), vec![9, 5]);
+        });
+    }
+
+    #[test]
+    fn pop_vec_with_remaining_test() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(MaxHeap::pop_vec_with_remaining(&0), (vec![], 0));
+            <HeapStore>::put([50, 40, 20, 10, 30].to_vec());
+            assert_eq!(MaxHeap::pop_vec_with_remaining(&60), (vec![], 5));
+            assert_eq!(MaxHeap::pop_vec_with_remaining(&25), (vec![50, 40, 30], 2));
+            assert_eq!(MaxHeap::len(), 2);
+            assert_eq!(MaxHeap::pop_vec_with_remaining(&0), (vec![20, 10], 0));
+        });
+    }
+
+    #[test]
+    fn pop_vec_within_test() {
+        with_externalities(&mut new_test_ext(), || {
+            <HeapStore>::put([50, 40, 20, 10, 30].to_vec());
+            let mut lens = Vec::new();
+            let popped = MaxHeap::pop_vec_within(&15, |len| {
+                lens.push(len);
+                lens.len() <= 2
+            });
+            assert_eq!(popped, (vec![50, 40], 3));
+            assert_eq!(lens, vec![5, 4, 3]);
+            assert_eq!(MaxHeap::pop_vec_within(&15, |_| true), (vec![30, 20], 1));
+            assert_eq!(MaxHeap::pop_vec_within(&15, |_| true), (vec![], 1));
+        });
+    }
+
+    #[test]
+    fn empty_push_vec_test() {
+        with_externalities(&mut new_test_ext(), || {
+            MaxHeap::push_vec(vec![]);
+            assert!(!<HeapStore>::exists());
+
+            <HeapStore>::put([50, 40, 20].to_vec());
+            MaxHeap::push_vec(vec![]);
+            assert_eq!(TemplateModule::heap_store(), [50, 40, 20].to_vec());
+        });
+    }
+
+    #[test]
+    fn pop_if_test() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(MaxHeap::pop_if(|_| true), None);
+            assert!(!<HeapStore>::exists());
+
+            MaxHeap::push_vec([10, 20, 30, 40, 50].to_vec());
+            let before = TemplateModule::heap_store();
+            assert_eq!(MaxHeap::pop_if(|x| *x < 50), None);
+            assert_eq!(TemplateModule::heap_store(), before);
+
+            assert_eq!(MaxHeap::pop_if(|x| *x >= 50), Some(50));
+            assert_eq!(MaxHeap::pop_if(|x| *x >= 50), None);
+            assert_eq!(MaxHeap::peek(), Some(40));
+            assert!(is_max_heap(&TemplateModule::heap_store()));
+        });
+    }
+
+    #[test]
+    fn repair_test() {
+        with_externalities(&mut new_test_ext(), || {
+            assert!(!MaxHeap::repair());
+
+            <HeapStore>::put([10, 50, 20, 40, 30, 60].to_vec());
+            assert!(MaxHeap::repair());
+            let repaired = TemplateModule::heap_store();
+            assert!(is_max_heap(&repaired));
+            let mut sorted = repaired.clone();
+            sorted.sort();
+            assert_eq!(sorted, [10, 20, 30, 40, 50, 60].to_vec());
+
+            assert!(!MaxHeap::repair());
+            assert_eq!(TemplateModule::heap_store(), repaired);
+            assert_eq!(MaxHeap::pop(), Some(60));
+        });
+    }
 }
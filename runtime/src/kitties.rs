@@ -1,31 +1,181 @@
 use crate::mtp;
 use crate::heap::{Compare, Heap};
 use codec::{Decode, Encode};
-use rstd::{result, cmp, vec::Vec};
-use sr_primitives::traits::{Hash, Zero, SaturatedConversion};
-use support::{decl_event, decl_module, decl_storage, dispatch::Result,
-              ensure, StorageMap, StorageValue, traits::Currency};
+use rstd::{result, cmp, ops, vec::Vec};
+use sr_primitives::Percent;
+use sr_primitives::weights::{SimpleDispatchInfo, Weight};
+use sr_primitives::traits::{CheckedAdd, Hash, Zero, Saturating, SaturatedConversion};
+use support::{decl_event, decl_module, decl_storage, dispatch::Result, ensure, Parameter, StorageMap, StorageValue,
+              storage::unhashed, traits::{Currency, EnsureOrigin, ExistenceRequirement, Get, Imbalance,
+              ReservableCurrency, WithdrawReason}};
 use system::ensure_signed;
+use primitives::offchain::StorageKind;
 use runtime_io::*;
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
 
 const ONE_MINUTE: u64 = 60_000;
 const ONE_DAY: u64 = 86_400_000;
 const BASE_YOUNG_FACTOR: u8 = 5;
 const BASE_MATURITY_FACTOR: u8 = 10;
 const BASE_OLDNESS_FACTOR: u8 = 5;
+// Dispatch weights. These are estimates, not measurements: there is no benchmarking
+// framework at this revision of substrate. Each storage read or write is counted as
+// 1_000, and every heap push or pop adds another 10_000 to cover its O(log n) sifting
+// over the encoded `LifespanArray`.
+const SET_PRICE_WEIGHT: u32 = 10_000;
+const TRANSFER_WEIGHT: u32 = 20_000;
+const MINT_WEIGHT: u32 = 40_000;
+const CREATE_KITTY_WEIGHT: u32 = MINT_WEIGHT + 5_000;
+const BUY_KITTY_WEIGHT: u32 = TRANSFER_WEIGHT + 10_000;
+const BREED_KITTY_WEIGHT: u32 = MINT_WEIGHT + 15_000;
+const ACCESSORY_WEIGHT: u32 = 10_000;
+const SET_NAME_WEIGHT: u32 = 10_000;
+const MAKE_OFFER_WEIGHT: u32 = 20_000;
+const CANCEL_OFFER_WEIGHT: u32 = 10_000;
+const ACCEPT_OFFER_WEIGHT: u32 = BUY_KITTY_WEIGHT + 5_000;
+const CANCEL_ALL_OFFERS_WEIGHT: u32 = 50_000;
+const SAFE_TRANSFER_WEIGHT: u32 = TRANSFER_WEIGHT + 2_000;
+const CREATOR_WEIGHT: u32 = 10_000;
+const SET_LIFE_FACTORS_WEIGHT: u32 = 10_000;
+const PROPOSE_SWAP_WEIGHT: u32 = 10_000;
+const ACCEPT_SWAP_WEIGHT: u32 = TRANSFER_WEIGHT * 2;
+const REBUILD_LIFESPAN_HEAP_WEIGHT: u32 = 200_000;
+const FORCE_BURN_WEIGHT: u32 = 100_000;
+const FAVORITE_WEIGHT: u32 = 10_000;
+const REINDEX_OWNED_WEIGHT: u32 = 200_000;
+const RECOUNT_OWNED_WEIGHT: u32 = 100_000;
+const BUY_MANY_WEIGHT: u32 = BUY_KITTY_WEIGHT * 10;
+/// Weight of reaping one expired kitty in `on_initialize`.
+const REAP_WEIGHT: Weight = 20_000;
+/// Weight of one sift step of a heap push or pop.
+const HEAP_STEP_WEIGHT: Weight = 1_000;
+/// Reaping in `on_initialize` may use up to `MaximumBlockWeight / REAP_BUDGET_DIVISOR`.
+/// Kitties due beyond that are reaped in the following blocks.
+const REAP_BUDGET_DIVISOR: Weight = 4;
 
-#[derive(PartialEq)]
-#[cfg_attr(feature = "std", derive(Debug))]
-enum LifeStage {
+/// Experience a kitty needs to reach each level above zero.
+const LEVEL_THRESHOLDS: [u32; 5] = [1, 3, 6, 10, 15];
+
+/// Number of distinct coat patterns and eye shapes a DNA can decode to.
+const PATTERN_COUNT: u8 = 8;
+const EYE_SHAPE_COUNT: u8 = 4;
+
+/// Layout version of the stored `Kitty`, bumped whenever a field is added.
+const CURRENT_STORAGE_VERSION: u32 = 5;
+/// Kitties `migrate` converts per block, so an upgrade can't stall block production.
+const MIGRATION_BATCH: u64 = 100;
+/// Off-chain local storage key holding the ids of kitties about to expire.
+pub const EXPIRING_KITTIES_KEY: &[u8] = b"kitties::expiring";
+
+/// Where a kitty is in its life, see `LifeStage::from_times`.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub enum LifeStage {
     Young,
     Maturity,
     Oldness,
     Invalid,
 }
 
-#[derive(Encode, Decode, Default, Clone, PartialEq)]
+impl LifeStage {
+    /// The stage of a kitty with `lifetime` at `now`. `Invalid` before birth and after death.
+    pub fn from_times<Moment: Ord>(now: Moment, lifetime: &Lifetime<Moment>) -> Self {
+        if now < lifetime.birth_time {
+            LifeStage::Invalid
+        } else if now < lifetime.maturity_time {
+            LifeStage::Young
+        } else if now < lifetime.old_time {
+            LifeStage::Maturity
+        } else if now < lifetime.end_time {
+            LifeStage::Oldness
+        } else {
+            LifeStage::Invalid
+        }
+    }
+}
+
+/// What happens to a kitty once its lifetime has ended.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum ExpiryMode {
+    /// Delete the kitty and all its bookkeeping.
+    Burn,
+    /// Keep the kitty with its owner, but never let it be sold, transferred or bred again.
+    Freeze,
+}
+
+/// How reaping in `on_initialize` is reported.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum ExpiryEvents {
+    /// One `Expired` event per reaped kitty.
+    PerKitty,
+    /// A single `ExpiredBatch` event per block.
+    Batch,
+}
+
+/// Visible traits decoded from a kitty's DNA, see `Module::phenotype`.
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub struct Phenotype {
+    /// RGB coat color, DNA bytes 3 to 5.
+    pub body_color: [u8; 3],
+    /// Coat pattern in `0..PATTERN_COUNT`, DNA byte 6.
+    pub pattern: u8,
+    /// Eye shape in `0..EYE_SHAPE_COUNT`, DNA byte 7.
+    pub eye_shape: u8,
+}
+
+/// A rare DNA pattern marking a kitty as a special edition.
+#[derive(Clone, PartialEq)]
 #[cfg_attr(feature = "std", derive(Debug))]
+pub struct FancyPattern {
+    /// Fancy id given to kitties matching this pattern.
+    pub id: u32,
+    /// Leading DNA bytes a kitty must have to match.
+    pub prefix: Vec<u8>,
+}
+
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
 pub struct Kitty<Hash, Balance, Moment> {
+    id: Hash,
+    dna: Hash,
+    /// Asking price, `None` when the kitty is not for sale.
+    price: Option<Balance>,
+    gen: u64,
+    lifetime: Lifetime<Moment>,
+    breed_count: u64,
+    /// When the kitty last changed hands, `None` if it never has.
+    last_transfer: Option<Moment>,
+}
+
+/// `Kitty` as stored in storage versions 2 and 3, before `last_transfer` was tracked.
+#[derive(Encode, Decode)]
+struct KittyV2<Hash, Balance, Moment> {
+    id: Hash,
+    dna: Hash,
+    price: Option<Balance>,
+    gen: u64,
+    lifetime: Lifetime<Moment>,
+    breed_count: u64,
+}
+
+/// `Kitty` as stored in storage version 1, when a zero price meant not for sale.
+#[derive(Encode, Decode)]
+struct KittyV1<Hash, Balance, Moment> {
+    id: Hash,
+    dna: Hash,
+    price: Balance,
+    gen: u64,
+    lifetime: Lifetime<Moment>,
+    breed_count: u64,
+}
+
+/// `Kitty` as stored before storage version 1, kept only to decode old entries.
+#[derive(Encode, Decode)]
+struct KittyV0<Hash, Balance, Moment> {
     id: Hash,
     dna: Hash,
     price: Balance,
@@ -33,26 +183,292 @@ pub struct Kitty<Hash, Balance, Moment> {
     lifetime: Lifetime<Moment>,
 }
 
+/// Everything a kitty's page shows, see `Module::kitty_details`.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub struct KittyDetails<Hash, Balance, Moment, AccountId> {
+    pub kitty: Kitty<Hash, Balance, Moment>,
+    pub owner: AccountId,
+    /// Life stage at the current median time past.
+    pub life_stage: LifeStage,
+    /// Zero once the kitty has reached its end time.
+    pub time_to_death: Moment,
+    pub name: Vec<u8>,
+}
+
 #[derive(Encode, Decode, Default, Clone, PartialEq)]
-#[cfg_attr(feature = "std", derive(Debug))]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
 pub struct Lifetime<Moment> {
-    birth_time: Moment,
-    maturity_time: Moment,
-    old_time: Moment,
-    end_time: Moment,
+    pub birth_time: Moment,
+    pub maturity_time: Moment,
+    pub old_time: Moment,
+    pub end_time: Moment,
 }
 
 #[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "std", derive(Debug))]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
 pub struct Lifespan<Hash, Moment> {
     kitty_id: Hash,
     end_time: Moment,
 }
 
-pub trait Trait: balances::Trait + mtp::Trait {
+/// Earliest `end_time` first like `EndTimeCompare`, ties broken by `kitty_id`.
+impl<Hash: Ord, Moment: Ord> Ord for Lifespan<Hash, Moment> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.end_time.cmp(&other.end_time).then_with(|| self.kitty_id.cmp(&other.kitty_id))
+    }
+}
+
+impl<Hash: Ord, Moment: Ord> PartialOrd for Lifespan<Hash, Moment> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A standing bid on a kitty. `amount` stays reserved from `buyer` until the offer
+/// is accepted, cancelled or expires.
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub struct Offer<AccountId, Balance, Moment> {
+    buyer: AccountId,
+    amount: Balance,
+    expires_at: Moment,
+}
+
+/// A one-for-one trade offered by `proposer` to `counterparty`, settled in full by
+/// `accept_swap` or not at all. `deposit` stays reserved from the proposer until then.
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct SwapProposal<AccountId, Hash, Balance> {
+    proposer: AccountId,
+    proposer_kitty: Hash,
+    counterparty: AccountId,
+    counterparty_kitty: Hash,
+    deposit: Balance,
+}
+
+/// Entry of `OfferExpiryHeap`, pointing at the offer of `buyer` on `kitty_id`.
+#[derive(Encode, Decode, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct OfferExpiry<Hash, AccountId, Moment> {
+    kitty_id: Hash,
+    buyer: AccountId,
+    expires_at: Moment,
+}
+
+pub trait Trait: balances::Trait + timestamp::Trait {
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+    /// Source of the median time past that drives every kitty's life stage.
+    type Time: mtp::MedianTime<Moment = Self::Moment>;
+    /// The cut of every sale taken by the marketplace.
+    type MarketplaceFee: Get<Percent>;
+    /// The account receiving the marketplace fee.
+    type FeeDestination: Get<Self::AccountId>;
+    /// Called synchronously whenever a kitty is minted.
+    type OnCreated: OnKittyCreated<Self::AccountId, Self::Hash>;
+    /// Called synchronously whenever a kitty changes hands.
+    type OnTransferred: OnKittyTransferred<Self::AccountId, Self::Hash>;
+    /// Whether the off-chain worker reports kitties about to expire.
+    type ReportExpiring: Get<bool>;
+    /// How far ahead of the median time past the off-chain worker looks for expiring kitties.
+    type ExpiryWarningWindow: Get<Self::Moment>;
+    /// How many kitties one account may breed per day.
+    type DailyBreedLimit: Get<u32>;
+    /// Maximum length in bytes of the memo attached to a transfer or purchase.
+    type MaxMemoLength: Get<u32>;
+    /// Whether expired kitties are burned or frozen.
+    type ExpiryBehavior: Get<ExpiryMode>;
+    /// Whether reaping emits an event per kitty or one summary per block.
+    type ExpiryEvents: Get<ExpiryEvents>;
+    /// Maximum number of accessories a kitty can wear at once.
+    type MaxAccessories: Get<u32>;
+    /// Free balance an account must hold, but does not spend, to create a kitty.
+    type MinBalanceToCreate: Get<Self::Balance>;
+    /// Maximum length in bytes of a kitty's name.
+    type MaxNameLength: Get<u32>;
+    /// DNA patterns that make a kitty fancy, checked in order.
+    type FancyPatterns: Get<Vec<FancyPattern>>;
+    /// How long a kitty must stay with its owner before it can change hands again.
+    type TransferCooldown: Get<Self::Moment>;
+    /// How long an offer keeps the buyer's funds reserved before it lapses.
+    type OfferTtl: Get<Self::Moment>;
+    /// Maximum number of open offers on a single kitty.
+    type MaxOffersPerKitty: Get<u32>;
+    /// Maximum number of kitties a single account may own.
+    type MaxKittiesOwned: Get<u64>;
+    /// Paid out of the `FeeDestination` pot for every kitty reaped in `on_initialize`.
+    type ReaperBounty: Get<Self::Balance>;
+    /// The account receiving the reaper bounty.
+    type ReaperRewardDestination: Get<Self::AccountId>;
+    /// Origin allowed to manage the creator whitelist.
+    type AdminOrigin: EnsureOrigin<Self::Origin>;
+    /// Whether only whitelisted `Creators` may create gen-0 kitties.
+    type CreatorGating: Get<bool>;
+    /// The cut of a sale paid to the account that referred the buyer.
+    type ReferralPercent: Get<Percent>;
+    /// Lowest asking price `set_price` accepts. Delisting is always allowed.
+    type MinListingPrice: Get<Self::Balance>;
+    /// How long after birth a kitty can be given away but not yet listed for sale.
+    type SaleGracePeriod: Get<Self::Moment>;
+    /// How many past owners `OwnerHistory` keeps per kitty.
+    type MaxOwnerHistory: Get<u32>;
+    /// Maximum number of kitties one account can favorite.
+    type MaxFavorites: Get<u32>;
+    /// Maximum number of accounts that can favorite one kitty, bounding `FavoritedBy`.
+    type MaxFavoritesPerKitty: Get<u32>;
+    /// Percent of its parents' generation lifetime a kitty gets, compounding per
+    /// generation. 100 disables scaling, below shortens and above lengthens lives.
+    type GenerationLifetimePercent: Get<u32>;
+    /// Maximum number of kitties `buy_many` buys at once.
+    type MaxBatchPurchases: Get<u32>;
+    /// Reserved from the proposer of a swap until it is accepted or cancelled.
+    type SwapDeposit: Get<Self::Balance>;
+    /// Account expired kitties are retired to instead of following `ExpiryBehavior`.
+    /// `None` keeps `ExpiryBehavior`.
+    type ExpiryDestination: Get<Option<Self::AccountId>>;
+    /// Fee for breeding two kitties that have never bred, paid to `FeeDestination`.
+    type BreedFeeBase: Get<Self::Balance>;
+    /// Added to the breeding fee for every earlier breed of either parent.
+    type BreedFeeStep: Get<Self::Balance>;
+    /// When in its life a kitty may breed, `()` for its whole maturity.
+    type BreedingWindow: BreedingWindow<Self::Moment>;
+    /// Cap on DNA byte 0, the minutes of childhood added to the base young factor.
+    type MaxYoungFactor: Get<u8>;
+    /// Cap on DNA byte 1, the days of maturity added to the base maturity factor.
+    type MaxMaturityFactor: Get<u8>;
+    /// Cap on DNA byte 2, the minutes of old age added to the base oldness factor.
+    type MaxOldnessFactor: Get<u8>;
+    /// How long a listing stays up before it is taken down automatically. Zero keeps
+    /// listings up until the kitty is sold, moved or dies.
+    type ListingTtl: Get<Self::Moment>;
+    /// Turns the random seed of a gen-0 kitty into its DNA, `()` uses the seed as is.
+    type DnaGenerator: DnaGenerator<Self::Hash>;
+    /// Pending operations elsewhere, e.g. a scheduler, that must not have a kitty moved
+    /// out from under them.
+    type Encumbrances: IsEncumbered<Self::Hash>;
+    type WeightInfo: WeightInfo;
+    /// Whether minting also emits `DnaSummary`, so clients can render a kitty from events alone.
+    type EmitDnaSummary: Get<bool>;
+    /// Identifier of the non-native assets kitties can be priced in.
+    type AssetId: Parameter + Copy;
+    /// Balances of the non-native assets.
+    type Assets: MultiAssets<Self::AccountId, Self::AssetId, Self::Balance>;
+}
+
+/// Minimal view of a multi-asset ledger, for selling kitties in tokens other than
+/// the native currency.
+pub trait MultiAssets<AccountId, AssetId, Balance> {
+    /// Whether `asset` exists, i.e. whether a listing priced in it could ever be settled.
+    fn exists(asset: AssetId) -> bool;
+    fn free_balance(asset: AssetId, who: &AccountId) -> Balance;
+    /// Whether `transfer` with the same arguments would succeed right now.
+    fn can_transfer(asset: AssetId, from: &AccountId, to: &AccountId, amount: Balance) -> Result;
+    fn transfer(asset: AssetId, from: &AccountId, to: &AccountId, amount: Balance) -> Result;
+}
+
+/// No non-native assets: none exist, every balance is zero and every transfer fails.
+impl<AccountId, AssetId, Balance: Zero> MultiAssets<AccountId, AssetId, Balance> for () {
+    fn exists(_: AssetId) -> bool { false }
+    fn free_balance(_: AssetId, _: &AccountId) -> Balance { Zero::zero() }
+    fn can_transfer(_: AssetId, _: &AccountId, _: &AccountId, _: Balance) -> Result {
+        Err("Non-native assets are not supported")
+    }
+    fn transfer(_: AssetId, _: &AccountId, _: &AccountId, _: Balance) -> Result {
+        Err("Non-native assets are not supported")
+    }
+}
+
+/// Hook for other modules to react to a newly minted kitty.
+pub trait OnKittyCreated<AccountId, Hash> {
+    fn on_created(owner: &AccountId, kitty_id: &Hash);
+}
+
+impl<AccountId, Hash> OnKittyCreated<AccountId, Hash> for () {
+    fn on_created(_: &AccountId, _: &Hash) {}
+}
+
+impl<AccountId, Hash, X, Y> OnKittyCreated<AccountId, Hash> for (X, Y)
+    where X: OnKittyCreated<AccountId, Hash>,
+          Y: OnKittyCreated<AccountId, Hash>,
+{
+    fn on_created(owner: &AccountId, kitty_id: &Hash) {
+        X::on_created(owner, kitty_id);
+        Y::on_created(owner, kitty_id);
+    }
+}
+
+/// Hook for other modules to react to a kitty changing hands.
+pub trait OnKittyTransferred<AccountId, Hash> {
+    fn on_transferred(from: &AccountId, to: &AccountId, kitty_id: &Hash);
+}
+
+impl<AccountId, Hash> OnKittyTransferred<AccountId, Hash> for () {
+    fn on_transferred(_: &AccountId, _: &AccountId, _: &Hash) {}
+}
+
+impl<AccountId, Hash, X, Y> OnKittyTransferred<AccountId, Hash> for (X, Y)
+    where X: OnKittyTransferred<AccountId, Hash>,
+          Y: OnKittyTransferred<AccountId, Hash>,
+{
+    fn on_transferred(from: &AccountId, to: &AccountId, kitty_id: &Hash) {
+        X::on_transferred(from, to, kitty_id);
+        Y::on_transferred(from, to, kitty_id);
+    }
+}
+
+/// Weights of the calls and hooks whose cost a runtime may want to benchmark.
+pub trait WeightInfo {
+    fn create_kitty() -> Weight;
+    fn breed_kitty() -> Weight;
+    /// Burning, freezing or retiring one expired kitty, heap work aside.
+    fn burn_one() -> Weight;
+    /// Popping from a heap of `len` elements, which sifts down up to log2(len) levels.
+    fn heap_pop(len: u32) -> Weight;
+}
+
+/// The estimated weights above, for runtimes without their own measurements.
+impl WeightInfo for () {
+    fn create_kitty() -> Weight { CREATE_KITTY_WEIGHT }
+    fn breed_kitty() -> Weight { BREED_KITTY_WEIGHT }
+    fn burn_one() -> Weight { REAP_WEIGHT }
+    fn heap_pop(len: u32) -> Weight {
+        HEAP_STEP_WEIGHT.saturating_mul(32 - len.leading_zeros())
+    }
+}
+
+/// Lets other modules hold a kitty in place while an operation on it is pending.
+pub trait IsEncumbered<Hash> {
+    fn is_encumbered(kitty_id: &Hash) -> bool;
 }
 
+impl<Hash> IsEncumbered<Hash> for () {
+    fn is_encumbered(_: &Hash) -> bool { false }
+}
+
+/// Derives the DNA of a gen-0 kitty from its random seed, e.g. to keep a drop
+/// within one color family. Bred kitties inherit their DNA instead.
+pub trait DnaGenerator<Hash> {
+    fn generate(seed: Hash) -> Hash;
+}
+
+impl<Hash> DnaGenerator<Hash> for () {
+    fn generate(seed: Hash) -> Hash { seed }
+}
+
+/// The part of a kitty's life in which it can breed, as `start..end` computed from
+/// its `Lifetime`. The end is capped at `end_time`. Defaults to the maturity stage.
+pub trait BreedingWindow<Moment: Clone> {
+    fn breeding_start(lifetime: &Lifetime<Moment>) -> Moment {
+        lifetime.maturity_time.clone()
+    }
+
+    fn breeding_end(lifetime: &Lifetime<Moment>) -> Moment {
+        lifetime.old_time.clone()
+    }
+}
+
+impl<Moment: Clone> BreedingWindow<Moment> for () {}
+
 pub struct EndTimeCompare<T> (rstd::marker::PhantomData<(T)>);
 
 impl<T: timestamp::Trait> Compare for EndTimeCompare<T> {
@@ -63,17 +479,80 @@ impl<T: timestamp::Trait> Compare for EndTimeCompare<T> {
 type LifespanHeap<T> = Heap<Lifespan<<T as system::Trait>::Hash, <T as timestamp::Trait>::Moment>,
     EndTimeCompare<T>, LifespanArray<T>>;
 
+pub struct ExpiresAtCompare<T> (rstd::marker::PhantomData<(T)>);
+
+impl<T: timestamp::Trait> Compare for ExpiresAtCompare<T> {
+    type A = OfferExpiry<<T as system::Trait>::Hash, <T as system::Trait>::AccountId, T::Moment>;
+    fn closer_than(x: &Self::A, y: &Self::A) -> bool { x.expires_at < y.expires_at }
+}
+
+/// Offers ordered by when they lapse. Entries of offers already accepted or
+/// cancelled are left in place and skipped once they reach the top.
+type OfferExpiryHeap<T> = Heap<OfferExpiry<<T as system::Trait>::Hash, <T as system::Trait>::AccountId,
+    <T as timestamp::Trait>::Moment>, ExpiresAtCompare<T>, OfferExpiryArray<T>>;
+
+/// Kitties ordered by when they enter `Oldness`. Reuses `Lifespan` with `end_time`
+/// holding the end of maturity, i.e. `Lifetime::old_time`.
+type OldnessHeap<T> = Heap<Lifespan<<T as system::Trait>::Hash, <T as timestamp::Trait>::Moment>,
+    EndTimeCompare<T>, OldnessArray<T>>;
+
+/// Listings ordered by when they lapse, again reusing `Lifespan`. Holds at most one
+/// entry per kitty. Entries of listings since taken down are skipped, see `ListingExpiresAt`.
+type ListingExpiryHeap<T> = Heap<Lifespan<<T as system::Trait>::Hash, <T as timestamp::Trait>::Moment>,
+    EndTimeCompare<T>, ListingExpiryArray<T>>;
+
+/// Funds withdrawn from an account and not yet credited anywhere.
+type NegativeImbalanceOf<T> = <balances::Module<T> as Currency<<T as system::Trait>::AccountId>>::NegativeImbalance;
+
 decl_event!(
     pub enum Event<T>
     where
         <T as system::Trait>::AccountId,
         <T as system::Trait>::Hash,
-        <T as balances::Trait>::Balance
+        <T as balances::Trait>::Balance,
+        <T as Trait>::AssetId
     {
         Created(AccountId, Hash),
-        PriceSet(AccountId, Hash, Balance),
-        Transferred(AccountId, AccountId, Hash),
-        Bought(AccountId, AccountId, Hash, Balance),
+        /// Companion of `Created` packing the kitty's phenotype, see `Module::dna_summary`.
+        DnaSummary(Hash, u8),
+        PriceSet(AccountId, Hash, Option<Balance>),
+        AssetPriceSet(AccountId, Hash, Balance, AssetId),
+        Transferred(AccountId, AccountId, Hash, Vec<u8>),
+        Bought(AccountId, AccountId, Hash, Balance, Vec<u8>),
+        /// Owner of the offspring, both parents and the offspring.
+        Bred(AccountId, Hash, Hash, Hash),
+        ListingExpired(Hash),
+        EnteringOldness(Hash),
+        AccessoryEquipped(Hash, u32),
+        AccessoryUnequipped(Hash, u32),
+        LeveledUp(Hash, u32),
+        NameSet(Hash, Vec<u8>),
+        FancyMinted(Hash, u32),
+        OfferMade(AccountId, Hash, Balance),
+        OfferCancelled(AccountId, Hash),
+        OfferAccepted(AccountId, AccountId, Hash, Balance),
+        OfferExpired(AccountId, Hash),
+        Expired(Hash),
+        ForceBurned(Hash),
+        /// Open offers on an expiring kitty were released, with how many.
+        OffersAbortedByExpiry(Hash, u32),
+        ExpiredBatch(u32, Vec<Hash>),
+        /// Lifespans still waiting in the heap after a reap removed some.
+        LifespanHeapPruned(u64),
+        AllOffersCancelled(Hash, u32),
+        CreatorAdded(AccountId),
+        CreatorRemoved(AccountId),
+        LifespanHeapRebuilt(u64),
+        OwnedKittiesReindexed(AccountId, u64),
+        OwnedKittiesRecounted(AccountId, u64),
+        Favorited(AccountId, Hash),
+        Unfavorited(AccountId, Hash),
+        /// Proposer, counterparty and the proposal id.
+        SwapProposed(AccountId, AccountId, u64),
+        SwapCancelled(u64),
+        SwapAccepted(u64),
+        /// New young, maturity and oldness base factors.
+        LifeFactorsSet(u8, u8, u8),
     }
 );
 
@@ -85,15 +564,94 @@ decl_storage! {
         AllKittiesArray get(kitty_by_index): map u64 => T::Hash;
         AllKittiesCount get(all_kitties_count): u64;
         AllKittiesIndex: map T::Hash => u64;
+        /// Kitties ever minted. Unlike `AllKittiesCount` it never goes down on burn.
+        TotalEverCreated get(total_ever_created): u64;
 
         OwnedKittiesArray get(kitty_of_owner_by_index): map (T::AccountId, u64) => T::Hash;
         OwnedKittiesCount get(owned_kitty_count): map T::AccountId => u64;
         OwnedKittiesIndex: map T::Hash => u64;
 
-        // As a storage only use for LifespanHeap. Do not modify it directly.
+        ForSaleArray get(for_sale_by_index): map u64 => T::Hash;
+        ForSaleCount get(for_sale_count): u64;
+        ForSaleIndex: map T::Hash => u64;
+        /// Asset a listed kitty is priced in, native currency when absent.
+        PriceAsset get(price_asset_of): map T::Hash => Option<T::AssetId>;
+        /// Owners of a kitty, oldest first and capped at `MaxOwnerHistory`. Includes the current owner.
+        OwnerHistory get(owner_history): map T::Hash => Vec<T::AccountId>;
+
+        /// Kitties an account has bookmarked, owned or not.
+        Favorites get(is_favorite): map (T::AccountId, T::Hash) => bool;
+        FavoriteCount get(favorite_count): map T::AccountId => u32;
+        /// Accounts that favorited a kitty, so a burn can clean up after them.
+        FavoritedBy get(favorited_by): map T::Hash => Vec<T::AccountId>;
+
+        // As a storage only use for LifespanHeap. Do not access it directly, read it
+        // through `LifespanHeap::to_vec`, `peek` and `len`. `LifespanHeap::repair` undoes
+        // direct writes that broke the heap order.
         LifespanArray: Vec<Lifespan<T::Hash, T::Moment>>;
+        // As a storage only use for OldnessHeap. Do not access it directly.
+        OldnessArray: Vec<Lifespan<T::Hash, T::Moment>>;
+        // As a storage only use for ListingExpiryHeap. Do not access it directly.
+        ListingExpiryArray: Vec<Lifespan<T::Hash, T::Moment>>;
+        /// When the current listing of a kitty lapses, if `ListingTtl` is set.
+        ListingExpiresAt get(listing_expires_at): map T::Hash => Option<T::Moment>;
 
         Nonce: u64;
+        /// Gen-0 kitties created in the current block, cleared in `on_finalize`.
+        CreatedThisBlock: u32;
+
+        /// Start of the account's current one day breeding window and the kitties bred in it.
+        BreedWindow get(breed_window): map T::AccountId => (T::Moment, u32);
+
+        /// Item ids of the cosmetic accessories a kitty wears.
+        Accessories get(accessories_of): map T::Hash => Vec<u32>;
+
+        /// Experience a kitty has earned by breeding and changing hands.
+        Experience get(experience_of): map T::Hash => u32;
+
+        /// Display name of a kitty, empty when unnamed.
+        Names get(name_of): map T::Hash => Vec<u8>;
+        /// Kitty holding each name, keyed by the normalized name so no two kitties share one.
+        NameOwner get(kitty_by_name): map Vec<u8> => Option<T::Hash>;
+
+        /// Fancy id of kitties minted with a rare DNA pattern.
+        Fancy get(fancy_of): map T::Hash => Option<u32>;
+
+        /// Outstanding offers on a kitty, at most one per buyer.
+        Offers get(offers_for): map T::Hash => Vec<Offer<T::AccountId, T::Balance, T::Moment>>;
+        // As a storage only use for OfferExpiryHeap. Do not modify it directly.
+        OfferExpiryArray: Vec<OfferExpiry<T::Hash, T::AccountId, T::Moment>>;
+
+        /// Open swap proposals by id.
+        SwapProposals get(swap_proposal): map u64 => Option<SwapProposal<T::AccountId, T::Hash, T::Balance>>;
+        /// Id handed to the next swap proposal.
+        NextSwapId get(next_swap_id): u64;
+        /// Ids of the open swap proposals involving a kitty, on either side.
+        SwapsByKitty get(swaps_for): map T::Hash => Vec<u64>;
+
+        /// Accounts allowed to create gen-0 kitties while `CreatorGating` is on.
+        Creators get(is_creator): map T::AccountId => bool;
+
+        /// Base life-phase factors that `generate_lifetime` adds DNA on top of. Changing
+        /// them only affects kitties born afterwards.
+        YoungFactor get(young_factor): u8 = BASE_YOUNG_FACTOR;
+        MaturityFactor get(maturity_factor): u8 = BASE_MATURITY_FACTOR;
+        OldnessFactor get(oldness_factor): u8 = BASE_OLDNESS_FACTOR;
+
+        /// Expired kitties kept around read-only under `ExpiryMode::Freeze`.
+        Frozen get(is_frozen): map T::Hash => bool;
+        /// How many of an account's kitties are frozen. They don't count toward `MaxKittiesOwned`.
+        FrozenKittyCount get(frozen_kitty_count): map T::AccountId => u64;
+        /// Kitties bound to their owner for good, see `gift_kitty`.
+        Soulbound get(is_soulbound): map T::Hash => bool;
+
+        /// Layout version of the values in `Kitties`, see `migrate`.
+        StorageVersion get(storage_version): u32;
+        /// Index into `AllKittiesArray` the current migration step resumes from.
+        MigrationCursor get(migration_cursor): u64;
+        /// Set while a migration is spread over several blocks. Calls are refused meanwhile,
+        /// as kitties not yet migrated can't be decoded in the current layout.
+        Migrating get(migrating): bool;
     }
 }
 
@@ -102,113 +660,198 @@ decl_module! {
 
         fn deposit_event() = default;
 
+        #[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::create_kitty())]
         fn create_kitty(origin) -> Result {
             let sender = ensure_signed(origin)?;
-            let nonce = <Nonce>::get();
-            let random_hash = (<system::Module<T>>::random_seed(), &sender, nonce)
-                .using_encoded(<T as system::Trait>::Hashing::hash);
+            Self::ensure_migrated()?;
 
-            let mtp = <mtp::Module<T>>::median_time_past();
-            let new_kitty = Kitty {
-                id: random_hash,
-                dna: random_hash,
-                price: Zero::zero(),
-                gen: 0,
-                lifetime: Self::generate_lifetime(mtp, random_hash)?,
-            };
+            Self::do_create(sender)?;
+
+            Ok(())
+        }
 
-            Self::mint(sender, random_hash, new_kitty)?;
+        /// Mint a gen-0 kitty straight to `to`, e.g. for promotions and airdrops. A
+        /// `soulbound` kitty, e.g. an achievement, can never be sold or given away.
+        #[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::create_kitty())]
+        fn gift_kitty(origin, to: T::AccountId, soulbound: bool) -> Result {
+            T::AdminOrigin::ensure_origin(origin)?;
+            Self::ensure_migrated()?;
 
-            <Nonce>::mutate(|n| *n += 1);
+            let kitty_id = Self::create_gen0(to)?;
+
+            if soulbound {
+                <Soulbound<T>>::insert(kitty_id, true);
+            }
 
             Ok(())
         }
 
-        fn set_price(origin, kitty_id: T::Hash, new_price: T::Balance) -> Result {
+        #[weight = SimpleDispatchInfo::FixedNormal(SET_PRICE_WEIGHT)]
+        fn set_price(origin, kitty_id: T::Hash, new_price: Option<T::Balance>) -> Result {
             let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
 
-            ensure!(<Kitties<T>>::exists(kitty_id), "This cat does not exist");
+            if let Some(price) = new_price {
+                ensure!(price >= T::MinListingPrice::get(), "The price is below the listing floor");
+            }
 
-            let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
-            ensure!(owner == sender, "You do not own this cat");
+            Self::update_price(&sender, kitty_id, new_price)?;
+            <PriceAsset<T>>::remove(kitty_id);
 
-            let mtp = <mtp::Module<T>>::median_time_past();
-            let mut kitty = Self::kitty(kitty_id);
-            ensure!(Self::could_transfer(mtp, &kitty),
-                "This cat is not in the life stage that can be transferred");
+            Self::deposit_event(RawEvent::PriceSet(sender, kitty_id, new_price));
 
-            kitty.price = new_price;
+            Ok(())
+        }
+
+        /// List a kitty priced in a non-native asset. `MinListingPrice` is in native
+        /// units and does not apply.
+        #[weight = SimpleDispatchInfo::FixedNormal(SET_PRICE_WEIGHT)]
+        fn set_price_in_asset(origin, kitty_id: T::Hash, new_price: T::Balance, asset_id: T::AssetId) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
 
-            <Kitties<T>>::insert(kitty_id, kitty);
+            ensure!(T::Assets::exists(asset_id), "This asset does not exist");
+            Self::update_price(&sender, kitty_id, Some(new_price))?;
+            <PriceAsset<T>>::insert(kitty_id, asset_id);
 
-            Self::deposit_event(RawEvent::PriceSet(sender, kitty_id, new_price));
+            Self::deposit_event(RawEvent::AssetPriceSet(sender, kitty_id, new_price, asset_id));
 
             Ok(())
         }
 
-        fn transfer(origin, to: T::AccountId, kitty_id: T::Hash) -> Result {
+        #[weight = SimpleDispatchInfo::FixedNormal(TRANSFER_WEIGHT)]
+        fn transfer(origin, to: T::AccountId, kitty_id: T::Hash, memo: Vec<u8>) -> Result {
             let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+
+            Self::ensure_memo(&memo)?;
 
             let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
             ensure!(owner == sender, "You do not own this kitty");
+            ensure!(!Self::is_frozen(kitty_id), "This cat is frozen");
+            ensure!(!Self::is_encumbered(kitty_id), "This cat has a pending operation");
+            ensure!(!Self::is_soulbound(kitty_id), "This cat is soulbound");
 
-            let mtp = <mtp::Module<T>>::median_time_past();
+            let mtp = T::Time::median_time_past();
             let kitty = Self::kitty(kitty_id);
             ensure!(Self::could_transfer(mtp, &kitty),
                 "This cat is not in the life stage that can be transferred");
+            ensure!(Self::past_transfer_cooldown(mtp, &kitty), "This cat changed hands too recently");
 
-            Self::transfer_from(sender, to, kitty_id)?;
+            Self::transfer_from(sender, to, kitty_id, memo)?;
 
             Ok(())
         }
 
-        fn buy_kitty(origin, kitty_id: T::Hash, max_price: T::Balance) -> Result {
+        /// Buy a listed kitty. A `referrer` other than the buyer earns `ReferralPercent`
+        /// of the price out of the seller's proceeds.
+        #[weight = SimpleDispatchInfo::FixedNormal(BUY_KITTY_WEIGHT)]
+        fn buy_kitty(origin, kitty_id: T::Hash, max_price: T::Balance, memo: Vec<u8>,
+            referrer: Option<T::AccountId>) -> Result
+        {
             let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
 
-            ensure!(<Kitties<T>>::exists(kitty_id), "This cat does not exist");
+            Self::ensure_memo(&memo)?;
+            ensure!(referrer.as_ref() != Some(&sender), "You can't refer yourself");
 
-            let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
-            ensure!(owner != sender, "You can't buy your own cat");
+            let (owner, kitty_price) = Self::check_purchase(&sender, kitty_id, max_price)?;
+            Self::ensure_can_receive(&sender)?;
+
+            match Self::price_asset_of(kitty_id) {
+                Some(asset_id) => Self::pay_in_asset(asset_id, &sender, &owner, kitty_price, referrer.as_ref())?,
+                None => Self::pay_for_kitty(&sender, &owner, kitty_price, referrer.as_ref())?,
+            }
 
-            let mut kitty = Self::kitty(kitty_id);
+            Self::complete_sale(sender, owner, kitty_id, kitty_price, memo);
 
-            let kitty_price = kitty.price;
-            ensure!(!kitty_price.is_zero(), "The cat you want to buy is not for sale");
-            ensure!(kitty_price <= max_price, "The cat you want to buy costs more than your max price");
+            Ok(())
+        }
 
-            <balances::Module<T> as Currency<_>>::transfer(&sender, &owner, kitty_price)?;
+        /// Buy several listed kitties, each with its own max price. Every purchase is
+        /// checked and the total paid up front, so either all go through or none do.
+        #[weight = SimpleDispatchInfo::FixedNormal(BUY_MANY_WEIGHT)]
+        fn buy_many(origin, purchases: Vec<(T::Hash, T::Balance)>) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
 
-            Self::transfer_from(owner.clone(), sender.clone(), kitty_id)
-                .expect("`owner` is shown to own the kitty; \
-                `owner` must have greater than 0 kitties, so transfer cannot cause underflow; \
-                `all_kitty_count` shares the same type as `owned_kitty_count` \
-                and minting ensure there won't ever be more than `max()` kitties, \
-                which means transfer cannot cause an overflow; \
-                qed");
+            ensure!(!purchases.is_empty(), "Nothing to buy");
+            ensure!(purchases.len() <= T::MaxBatchPurchases::get() as usize, "Too many purchases in one batch");
+            ensure!(Self::live_kitty_count(&sender).saturating_add(purchases.len() as u64) <= T::MaxKittiesOwned::get(),
+                "The recipient owns too many kitties");
 
-            kitty.price = Zero::zero();
-            <Kitties<T>>::insert(kitty_id, kitty);
+            let mut sales = Vec::with_capacity(purchases.len());
+            let mut native_total = T::Balance::zero();
+            let mut asset_totals: Vec<(T::AssetId, T::Balance)> = Vec::new();
+            for (kitty_id, max_price) in purchases {
+                ensure!(!sales.iter().any(|(id, _, _, _)| *id == kitty_id), "The same cat is in the batch twice");
+                let (owner, price) = Self::check_purchase(&sender, kitty_id, max_price)?;
+                let asset = Self::price_asset_of(kitty_id);
+                match asset {
+                    Some(asset_id) => {
+                        Self::check_asset_payment(asset_id, &sender, &owner, price, None)?;
+                        match asset_totals.iter_mut().find(|(id, _)| *id == asset_id) {
+                            Some((_, total)) => *total = total.checked_add(&price).ok_or("Overflow totalling the batch")?,
+                            None => asset_totals.push((asset_id, price)),
+                        }
+                    },
+                    None => native_total = native_total.checked_add(&price).ok_or("Overflow totalling the batch")?,
+                }
+                sales.push((kitty_id, owner, price, asset));
+            }
+            for (asset_id, total) in &asset_totals {
+                ensure!(T::Assets::free_balance(*asset_id, &sender) >= *total, "Not enough balance in the price asset");
+            }
 
-            Self::deposit_event(RawEvent::Bought(sender, owner, kitty_id, kitty_price));
+            // Dispatch is not transactional, so every asset leg is paid before any kitty
+            // changes hands. Should one fail despite the checks above, the native payment goes
+            // back to the buyer and no sale completes; asset legs already paid stay paid.
+            let mut payment = <balances::Module<T> as Currency<_>>::withdraw(
+                &sender, native_total, WithdrawReason::Transfer, ExistenceRequirement::KeepAlive)?;
+            for (_, owner, price, asset) in &sales {
+                if let Some(asset_id) = asset {
+                    if let Err(e) = Self::pay_in_asset(*asset_id, &sender, owner, *price, None) {
+                        <balances::Module<T> as Currency<_>>::resolve_creating(&sender, payment);
+                        return Err(e);
+                    }
+                }
+            }
+            for (kitty_id, owner, price, asset) in sales {
+                if asset.is_none() {
+                    let (this_sale, rest) = payment.split(price);
+                    payment = rest;
+                    Self::distribute_payment(&owner, this_sale, price, None);
+                }
+                Self::complete_sale(sender.clone(), owner, kitty_id, price, Vec::new());
+            }
 
             Ok(())
         }
 
+        #[weight = SimpleDispatchInfo::FixedNormal(T::WeightInfo::breed_kitty())]
         fn breed_kitty(origin, kitty_id_1: T::Hash, kitty_id_2: T::Hash) -> Result{
             let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
 
             ensure!(<Kitties<T>>::exists(kitty_id_1), "This cat 1 does not exist");
             ensure!(<Kitties<T>>::exists(kitty_id_2), "This cat 2 does not exist");
 
+            let owner_1 = Self::owner_of(kitty_id_1).ok_or("No owner for this kitty")?;
+            let owner_2 = Self::owner_of(kitty_id_2).ok_or("No owner for this kitty")?;
+            ensure!(owner_1 == sender || owner_2 == sender, "You do not own either cat");
+
             let kitty_1 = Self::kitty(kitty_id_1);
             let kitty_2 = Self::kitty(kitty_id_2);
 
-            let mtp = <mtp::Module<T>>::median_time_past();
+            let mtp = T::Time::median_time_past();
             ensure!(Self::could_breed(mtp, &kitty_1),
                 "This cat 1 is not in the life stage that can be breed");
             ensure!(Self::could_breed(mtp, &kitty_2),
                 "This cat 2 is not in the life stage that can be breed");
 
+            let (window_start, bred) = Self::current_breed_window(mtp, &sender);
+            ensure!(bred < T::DailyBreedLimit::get(), "This account has reached its daily breeding limit");
+
             let nonce = <Nonce>::get();
             let random_hash = (<system::Module<T>>::random_seed(), &sender, nonce)
                 .using_encoded(<T as system::Trait>::Hashing::hash);
@@ -220,356 +863,4677 @@ decl_module! {
                 }
             }
 
+            let gen = cmp::max(kitty_1.gen, kitty_2.gen).checked_add(1)
+                .ok_or("Overflow calculating the generation of the new kitty")?;
             let new_kitty = Kitty {
                 id: random_hash,
                 dna: final_dna,
-                price: Zero::zero(),
-                gen: cmp::max(kitty_1.gen, kitty_2.gen) + 1,
-                lifetime: Self::generate_lifetime(mtp, final_dna)?,
+                price: None,
+                gen,
+                lifetime: Self::generate_lifetime(mtp, final_dna, gen)?,
+                breed_count: 0,
+                last_transfer: None,
             };
 
-            Self::mint(sender, random_hash, new_kitty)?;
+            let breed_fee = Self::breed_fee(&kitty_1, &kitty_2);
+            let fee = <balances::Module<T> as Currency<_>>::withdraw(
+                &sender, breed_fee, WithdrawReason::Fee, ExistenceRequirement::KeepAlive)?;
+            if let Err(e) = Self::mint(sender.clone(), random_hash, new_kitty) {
+                <balances::Module<T> as Currency<_>>::resolve_creating(&sender, fee);
+                return Err(e);
+            }
+            <balances::Module<T> as Currency<_>>::resolve_creating(&T::FeeDestination::get(), fee);
+
+            <BreedWindow<T>>::insert(&sender, (window_start, bred + 1));
+            <Kitties<T>>::mutate(kitty_id_1, |kitty| kitty.breed_count = kitty.breed_count.saturating_add(1));
+            <Kitties<T>>::mutate(kitty_id_2, |kitty| kitty.breed_count = kitty.breed_count.saturating_add(1));
+            Self::gain_experience(kitty_id_1);
+            Self::gain_experience(kitty_id_2);
 
             <Nonce>::mutate(|n| *n += 1);
 
-            Ok(())
-        }
+            Self::deposit_event(RawEvent::Bred(sender, kitty_id_1, kitty_id_2, random_hash));
 
-        fn on_finalize(_n: T::BlockNumber) {
-            let mtp = <mtp::Module<T>>::median_time_past();
-            Self::remove_expired_kitties(mtp);
+            Ok(())
         }
-    }
-}
 
-impl<T: Trait> Module<T> {
-    fn generate_lifetime(mtp: T::Moment, dna: T::Hash) -> result::Result<Lifetime<T::Moment>, &'static str> {
-        let birth_time = mtp.saturated_into::<u64>();
-        let maturity_time = birth_time.checked_add(ONE_MINUTE * u64::from(BASE_YOUNG_FACTOR + dna.as_ref()[0]))
-            .ok_or("Overflow calculating the childhood for a new kitty")?;
-        let old_time = maturity_time.checked_add(ONE_DAY* u64::from(BASE_MATURITY_FACTOR + dna.as_ref()[1]))
-            .ok_or("Overflow calculating the manhood for a new kitty")?;
-        let end_time = old_time.checked_add(ONE_MINUTE * u64::from(BASE_OLDNESS_FACTOR + dna.as_ref()[2]))
-            .ok_or("Overflow calculating the old age for a new kitty")?;
+        #[weight = SimpleDispatchInfo::FixedNormal(ACCESSORY_WEIGHT)]
+        fn equip_accessory(origin, kitty_id: T::Hash, item: u32) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
 
-        let lifetime = Lifetime {
-            birth_time: mtp,
-            maturity_time: maturity_time.saturated_into(),
-            old_time: old_time.saturated_into(),
-            end_time: end_time.saturated_into(),
-        };
+            let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
+            ensure!(owner == sender, "You do not own this cat");
+            ensure!(!Self::is_frozen(kitty_id), "This cat is frozen");
 
-        Ok(lifetime)
-    }
+            let mut accessories = Self::accessories_of(kitty_id);
+            ensure!(!accessories.contains(&item), "This accessory is already equipped");
+            ensure!(accessories.len() < T::MaxAccessories::get() as usize,
+                "This cat cannot wear any more accessories");
 
-    fn life_stage(mtp: T::Moment, lifetime: &Lifetime<T::Moment>) -> LifeStage {
-        if mtp.cmp(&lifetime.birth_time) == cmp::Ordering::Less {
-            LifeStage::Invalid
-        } else if mtp.cmp(&lifetime.maturity_time) == cmp::Ordering::Less {
-            LifeStage::Young
-        } else if mtp.cmp(&lifetime.old_time) == cmp::Ordering::Less {
-            LifeStage::Maturity
-        } else if mtp.cmp(&lifetime.end_time) == cmp::Ordering::Less {
-            LifeStage::Oldness
-        } else {
-            LifeStage::Invalid
-        }
-    }
+            accessories.push(item);
+            <Accessories<T>>::insert(kitty_id, accessories);
 
-    fn could_breed(mtp: T::Moment, kitty: &Kitty<T::Hash, T::Balance, T::Moment>) -> bool {
-        Self::life_stage(mtp, &kitty.lifetime) == LifeStage::Maturity
-    }
+            Self::deposit_event(RawEvent::AccessoryEquipped(kitty_id, item));
 
-    fn could_transfer(mtp: T::Moment, kitty: &Kitty<T::Hash, T::Balance, T::Moment>) -> bool {
-        match Self::life_stage(mtp, &kitty.lifetime) {
-            LifeStage::Young => true,
-            LifeStage::Maturity => true,
-            _ => false
+            Ok(())
         }
-    }
-
-    fn mint(to: T::AccountId, kitty_id: T::Hash, new_kitty: Kitty<T::Hash, T::Balance, T::Moment>) -> Result {
-        ensure!(!<KittyOwner<T>>::exists(kitty_id), "Kitty already exists");
 
-        let owned_kitty_count = Self::owned_kitty_count(&to);
+        #[weight = SimpleDispatchInfo::FixedNormal(ACCESSORY_WEIGHT)]
+        fn unequip_accessory(origin, kitty_id: T::Hash, item: u32) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
 
-        let new_owned_kitty_count = owned_kitty_count.checked_add(1)
-            .ok_or("Overflow adding a new kitty to account balance")?;
+            let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
+            ensure!(owner == sender, "You do not own this cat");
+            ensure!(!Self::is_frozen(kitty_id), "This cat is frozen");
 
-        let all_kitties_count = Self::all_kitties_count();
+            let mut accessories = Self::accessories_of(kitty_id);
+            let position = accessories.iter().position(|equipped| *equipped == item)
+                .ok_or("This accessory is not equipped")?;
 
-        let new_all_kitties_count = all_kitties_count.checked_add(1)
-            .ok_or("Overflow adding a new kitty to total supply")?;
+            accessories.remove(position);
+            if accessories.is_empty() {
+                <Accessories<T>>::remove(kitty_id);
+            } else {
+                <Accessories<T>>::insert(kitty_id, accessories);
+            }
 
-        <Kitties<T>>::insert(kitty_id, &new_kitty);
-        <KittyOwner<T>>::insert(kitty_id, &to);
+            Self::deposit_event(RawEvent::AccessoryUnequipped(kitty_id, item));
 
-        <AllKittiesArray<T>>::insert(all_kitties_count, kitty_id);
-        <AllKittiesCount>::put(new_all_kitties_count);
-        <AllKittiesIndex<T>>::insert(kitty_id, all_kitties_count);
+            Ok(())
+        }
 
-        <OwnedKittiesArray<T>>::insert((to.clone(), owned_kitty_count), kitty_id);
-        <OwnedKittiesCount<T>>::insert(&to, new_owned_kitty_count);
-        <OwnedKittiesIndex<T>>::insert(kitty_id, owned_kitty_count);
+        /// Name a kitty, or clear its name with an empty one. Names are unique across all
+        /// kitties, ignoring case and surrounding whitespace.
+        #[weight = SimpleDispatchInfo::FixedNormal(SET_NAME_WEIGHT)]
+        fn set_name(origin, kitty_id: T::Hash, name: Vec<u8>) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
 
-        <LifespanHeap<T>>::push(Lifespan {
-            kitty_id,
-            end_time: new_kitty.lifetime.end_time,
-        });
+            ensure!(name.len() <= T::MaxNameLength::get() as usize, "The name is too long");
 
-        Self::deposit_event(RawEvent::Created(to, kitty_id));
+            let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
+            ensure!(owner == sender, "You do not own this cat");
+            ensure!(!Self::is_frozen(kitty_id), "This cat is frozen");
 
-        Ok(())
-    }
+            let normalized = Self::normalize_name(&name);
+            if !normalized.is_empty() {
+                if let Some(holder) = Self::kitty_by_name(&normalized) {
+                    ensure!(holder == kitty_id, "This name is already taken");
+                }
+            }
 
-    fn transfer_from(from: T::AccountId, to: T::AccountId, kitty_id: T::Hash) -> Result {
-        let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
+            Self::release_name(kitty_id);
+            if !normalized.is_empty() {
+                <Names<T>>::insert(kitty_id, &name);
+                <NameOwner<T>>::insert(&normalized, kitty_id);
+            }
 
-        ensure!(owner == from, "'from' account does not own this kitty");
+            Self::deposit_event(RawEvent::NameSet(kitty_id, name));
 
-        let owned_kitty_count_from = Self::owned_kitty_count(&from);
-        let owned_kitty_count_to = Self::owned_kitty_count(&to);
+            Ok(())
+        }
 
-        let new_owned_kitty_count_to = owned_kitty_count_to.checked_add(1)
-            .ok_or("Transfer causes overflow of 'to' kitty balance")?;
+        /// Like `transfer`, but checks up front that `to` is an existing account with
+        /// room for another kitty, so nothing is touched unless the transfer goes through.
+        #[weight = SimpleDispatchInfo::FixedNormal(SAFE_TRANSFER_WEIGHT)]
+        fn safe_transfer(origin, to: T::AccountId, kitty_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+
+            let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
+            ensure!(owner == sender, "You do not own this kitty");
+            ensure!(!Self::is_frozen(kitty_id), "This cat is frozen");
+            ensure!(!Self::is_encumbered(kitty_id), "This cat has a pending operation");
+            ensure!(!Self::is_soulbound(kitty_id), "This cat is soulbound");
+
+            let mtp = T::Time::median_time_past();
+            let kitty = Self::kitty(kitty_id);
+            ensure!(Self::could_transfer(mtp, &kitty),
+                "This cat is not in the life stage that can be transferred");
+            ensure!(Self::past_transfer_cooldown(mtp, &kitty), "This cat changed hands too recently");
+
+            ensure!(!<balances::Module<T> as Currency<_>>::total_balance(&to).is_zero(),
+                "The recipient account does not exist");
+            Self::ensure_can_receive(&to)?;
+
+            Self::transfer_from(sender, to, kitty_id, Vec::new())?;
+
+            Ok(())
+        }
+
+        /// Move an old kitty, which `transfer` refuses, e.g. to consolidate kitties before a
+        /// planned burn. Gifts only: an old kitty still can't be listed or sold.
+        #[weight = SimpleDispatchInfo::FixedNormal(TRANSFER_WEIGHT)]
+        fn rescue_transfer(origin, to: T::AccountId, kitty_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+
+            let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
+            ensure!(owner == sender, "You do not own this kitty");
+            ensure!(!Self::is_frozen(kitty_id), "This cat is frozen");
+            ensure!(!Self::is_encumbered(kitty_id), "This cat has a pending operation");
+            ensure!(!Self::is_soulbound(kitty_id), "This cat is soulbound");
+
+            let mtp = T::Time::median_time_past();
+            let kitty = Self::kitty(kitty_id);
+            ensure!(Self::is_alive(mtp, &kitty), "This cat is no longer alive");
+            ensure!(Self::past_transfer_cooldown(mtp, &kitty), "This cat changed hands too recently");
+
+            Self::transfer_from(sender, to, kitty_id, Vec::new())?;
+
+            Ok(())
+        }
+
+        /// Bid `amount` on a kitty, reserving it until the offer is accepted, cancelled
+        /// or lapses after `OfferTtl`.
+        #[weight = SimpleDispatchInfo::FixedNormal(MAKE_OFFER_WEIGHT)]
+        fn make_offer(origin, kitty_id: T::Hash, amount: T::Balance) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+
+            let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
+            ensure!(owner != sender, "You can't make an offer on your own cat");
+            ensure!(!Self::is_frozen(kitty_id), "This cat is frozen");
+            ensure!(!Self::is_soulbound(kitty_id), "This cat is soulbound");
+            ensure!(Self::is_alive(T::Time::median_time_past(), &Self::kitty(kitty_id)), "This cat is no longer alive");
+            ensure!(!amount.is_zero(), "The offer must be more than zero");
+
+            let mut offers = Self::offers_for(kitty_id);
+            ensure!(!offers.iter().any(|offer| offer.buyer == sender), "You already have an offer on this cat");
+            ensure!(offers.len() < T::MaxOffersPerKitty::get() as usize, "This cat has too many offers");
+
+            <balances::Module<T> as ReservableCurrency<_>>::reserve(&sender, amount)?;
+
+            let expires_at = T::Time::median_time_past().saturating_add(T::OfferTtl::get());
+            offers.push(Offer { buyer: sender.clone(), amount, expires_at });
+            <Offers<T>>::insert(kitty_id, offers);
+            <OfferExpiryHeap<T>>::push(OfferExpiry { kitty_id, buyer: sender.clone(), expires_at });
+
+            Self::deposit_event(RawEvent::OfferMade(sender, kitty_id, amount));
+
+            Ok(())
+        }
+
+        #[weight = SimpleDispatchInfo::FixedNormal(CANCEL_OFFER_WEIGHT)]
+        fn cancel_offer(origin, kitty_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+
+            let offer = Self::take_offer(kitty_id, |offer| offer.buyer == sender)
+                .ok_or("You have no offer on this cat")?;
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&sender, offer.amount);
+
+            Self::deposit_event(RawEvent::OfferCancelled(sender, kitty_id));
+
+            Ok(())
+        }
+
+        #[weight = SimpleDispatchInfo::FixedNormal(ACCEPT_OFFER_WEIGHT)]
+        fn accept_offer(origin, kitty_id: T::Hash, buyer: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+
+            let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
+            ensure!(owner == sender, "You do not own this cat");
+            ensure!(buyer != owner, "You can't accept your own offer");
+            ensure!(!Self::is_frozen(kitty_id), "This cat is frozen");
+            ensure!(!Self::is_encumbered(kitty_id), "This cat has a pending operation");
+            ensure!(!Self::is_soulbound(kitty_id), "This cat is soulbound");
+
+            let mtp = T::Time::median_time_past();
+            let kitty = Self::kitty(kitty_id);
+            ensure!(Self::could_transfer(mtp, &kitty),
+                "This cat is not in the life stage that can be transferred");
+            ensure!(Self::past_transfer_cooldown(mtp, &kitty), "This cat changed hands too recently");
+
+            let offer = Self::offers_for(kitty_id).into_iter()
+                .find(|offer| offer.buyer == buyer)
+                .ok_or("There is no offer from this buyer")?;
+            ensure!(offer.expires_at > mtp, "This offer has expired");
+            Self::ensure_can_receive(&buyer)?;
+
+            // Pay straight out of the reserve and only then drop the offer. A shortfall means
+            // the offer is not fully backed, so whatever was taken goes back into the reserve.
+            let (payment, shortfall) =
+                <balances::Module<T> as ReservableCurrency<_>>::slash_reserved(&buyer, offer.amount);
+            if !shortfall.is_zero() {
+                let taken = payment.peek();
+                <balances::Module<T> as Currency<_>>::resolve_creating(&buyer, payment);
+                if <balances::Module<T> as ReservableCurrency<_>>::reserve(&buyer, taken).is_err() {
+                    runtime_io::print("accept_offer(): Could not restore the buyer's reserve");
+                }
+                return Err("The offer is no longer backed by reserved funds");
+            }
+            Self::distribute_payment(&owner, payment, offer.amount, None);
+            Self::take_offer(kitty_id, |offer| offer.buyer == buyer);
+
+            Self::transfer_from(owner.clone(), buyer.clone(), kitty_id, Vec::new())?;
+
+            <Kitties<T>>::mutate(kitty_id, |kitty| kitty.price = None);
+            Self::index_listing(kitty_id, false);
+
+            Self::deposit_event(RawEvent::OfferAccepted(owner, buyer, kitty_id, offer.amount));
+
+            Ok(())
+        }
+
+        /// Reject every outstanding offer on a kitty, releasing the bidders' funds.
+        #[weight = SimpleDispatchInfo::FixedNormal(CANCEL_ALL_OFFERS_WEIGHT)]
+        fn cancel_all_offers(origin, kitty_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+
+            let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
+            ensure!(owner == sender, "You do not own this cat");
+
+            let cancelled = Self::release_offers(kitty_id);
+
+            Self::deposit_event(RawEvent::AllOffersCancelled(kitty_id, cancelled));
+
+            Ok(())
+        }
+
+        /// Offer `my_kitty` to `counterparty` in exchange for `their_kitty`. Nothing moves
+        /// until the counterparty accepts.
+        #[weight = SimpleDispatchInfo::FixedNormal(PROPOSE_SWAP_WEIGHT)]
+        fn propose_swap(origin, my_kitty: T::Hash, their_kitty: T::Hash, counterparty: T::AccountId) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+
+            ensure!(sender != counterparty, "You can't swap with yourself");
+            ensure!(Self::owner_of(my_kitty) == Some(sender.clone()), "You do not own this cat");
+            ensure!(Self::owner_of(their_kitty) == Some(counterparty.clone()),
+                "The counterparty does not own this cat");
+
+            let mtp = T::Time::median_time_past();
+            Self::ensure_tradable(mtp, my_kitty)?;
+            Self::ensure_tradable(mtp, their_kitty)?;
+
+            let proposal_id = Self::next_swap_id();
+            let next_id = proposal_id.checked_add(1).ok_or("Swap id overflow")?;
+
+            let deposit = T::SwapDeposit::get();
+            <balances::Module<T> as ReservableCurrency<_>>::reserve(&sender, deposit)?;
+
+            <SwapProposals<T>>::insert(proposal_id, SwapProposal {
+                proposer: sender.clone(),
+                proposer_kitty: my_kitty,
+                counterparty: counterparty.clone(),
+                counterparty_kitty: their_kitty,
+                deposit,
+            });
+            <SwapsByKitty<T>>::mutate(my_kitty, |ids| ids.push(proposal_id));
+            <SwapsByKitty<T>>::mutate(their_kitty, |ids| ids.push(proposal_id));
+            NextSwapId::put(next_id);
+
+            Self::deposit_event(RawEvent::SwapProposed(sender, counterparty, proposal_id));
+
+            Ok(())
+        }
+
+        #[weight = SimpleDispatchInfo::FixedNormal(PROPOSE_SWAP_WEIGHT)]
+        fn cancel_swap(origin, proposal_id: u64) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+
+            let proposal = Self::swap_proposal(proposal_id).ok_or("There is no such swap proposal")?;
+            ensure!(sender == proposal.proposer || sender == proposal.counterparty,
+                "You are not a party to this swap");
+
+            Self::close_swap(proposal_id, &proposal);
+
+            Self::deposit_event(RawEvent::SwapCancelled(proposal_id));
+
+            Ok(())
+        }
+
+        /// Settle a swap proposed to the sender, moving both kitties or neither.
+        #[weight = SimpleDispatchInfo::FixedNormal(ACCEPT_SWAP_WEIGHT)]
+        fn accept_swap(origin, proposal_id: u64) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+
+            let proposal = Self::swap_proposal(proposal_id).ok_or("There is no such swap proposal")?;
+            ensure!(sender == proposal.counterparty, "This swap was not proposed to you");
+
+            ensure!(Self::owner_of(proposal.proposer_kitty) == Some(proposal.proposer.clone()),
+                "The proposer no longer owns their cat");
+            ensure!(Self::owner_of(proposal.counterparty_kitty) == Some(sender.clone()),
+                "You no longer own this cat");
+
+            let mtp = T::Time::median_time_past();
+            Self::ensure_tradable(mtp, proposal.proposer_kitty)?;
+            Self::ensure_tradable(mtp, proposal.counterparty_kitty)?;
+
+            // Each side gives one kitty for one, so neither ends up over `MaxKittiesOwned`.
+            Self::close_swap(proposal_id, &proposal);
+            Self::move_kitty(proposal.proposer.clone(), sender.clone(), proposal.proposer_kitty, Vec::new())
+                .expect("the proposer is shown to own the kitty; qed");
+            Self::move_kitty(sender, proposal.proposer, proposal.counterparty_kitty, Vec::new())
+                .expect("the counterparty is shown to own the kitty; qed");
+
+            Self::deposit_event(RawEvent::SwapAccepted(proposal_id));
+
+            Ok(())
+        }
+
+        /// Retune the base life-phase factors. Kitties already born keep their lifetimes.
+        /// None may go below its `BASE_*` default, so every life phase keeps a floor.
+        #[weight = SimpleDispatchInfo::FixedNormal(SET_LIFE_FACTORS_WEIGHT)]
+        fn set_life_factors(origin, young: u8, maturity: u8, oldness: u8) -> Result {
+            T::AdminOrigin::ensure_origin(origin)?;
+            Self::ensure_migrated()?;
+
+            ensure!(young >= BASE_YOUNG_FACTOR, "The young factor is below its base");
+            ensure!(maturity >= BASE_MATURITY_FACTOR, "The maturity factor is below its base");
+            ensure!(oldness >= BASE_OLDNESS_FACTOR, "The oldness factor is below its base");
+
+            YoungFactor::put(young);
+            MaturityFactor::put(maturity);
+            OldnessFactor::put(oldness);
+
+            Self::deposit_event(RawEvent::LifeFactorsSet(young, maturity, oldness));
+
+            Ok(())
+        }
+
+        #[weight = SimpleDispatchInfo::FixedNormal(CREATOR_WEIGHT)]
+        fn add_creator(origin, who: T::AccountId) -> Result {
+            T::AdminOrigin::ensure_origin(origin)?;
+            Self::ensure_migrated()?;
+
+            <Creators<T>>::insert(&who, true);
+
+            Self::deposit_event(RawEvent::CreatorAdded(who));
+
+            Ok(())
+        }
+
+        #[weight = SimpleDispatchInfo::FixedNormal(CREATOR_WEIGHT)]
+        fn remove_creator(origin, who: T::AccountId) -> Result {
+            T::AdminOrigin::ensure_origin(origin)?;
+            Self::ensure_migrated()?;
+
+            <Creators<T>>::remove(&who);
+
+            Self::deposit_event(RawEvent::CreatorRemoved(who));
+
+            Ok(())
+        }
+
+        /// Replace the lifespan heap with the lifespans of the live kitties, dropping
+        /// stale entries and restoring missing ones.
+        #[weight = SimpleDispatchInfo::FixedNormal(REBUILD_LIFESPAN_HEAP_WEIGHT)]
+        fn rebuild_lifespan_heap(origin) -> Result {
+            T::AdminOrigin::ensure_origin(origin)?;
+            Self::ensure_migrated()?;
+
+            let rebuilt = Self::rebuild_lifespans();
+
+            Self::deposit_event(RawEvent::LifespanHeapRebuilt(rebuilt));
+
+            Ok(())
+        }
+
+        /// Destroy a kitty regardless of its owner, e.g. one with an abusive name.
+        #[weight = SimpleDispatchInfo::FixedNormal(FORCE_BURN_WEIGHT)]
+        fn force_burn(origin, kitty_id: T::Hash) -> Result {
+            T::AdminOrigin::ensure_origin(origin)?;
+            Self::ensure_migrated()?;
+
+            ensure!(<Kitties<T>>::exists(kitty_id), "This cat does not exist");
+
+            <LifespanHeap<T>>::retain(|lifespan| lifespan.kitty_id != kitty_id);
+            Self::burn_token(kitty_id);
+
+            Self::deposit_event(RawEvent::ForceBurned(kitty_id));
+
+            Ok(())
+        }
+
+        /// Rebuild `account`'s owned-kitties enumeration from `KittyOwner`, the source of truth.
+        #[weight = SimpleDispatchInfo::FixedNormal(REINDEX_OWNED_WEIGHT)]
+        fn reindex_owned(origin, account: T::AccountId) -> Result {
+            T::AdminOrigin::ensure_origin(origin)?;
+            Self::ensure_migrated()?;
+
+            let count = Self::reindex_owned_kitties(&account);
+
+            Self::deposit_event(RawEvent::OwnedKittiesReindexed(account, count));
+
+            Ok(())
+        }
+
+        /// Correct `account`'s `OwnedKittiesCount` from `KittyOwner`. Admin only, as it
+        /// scans every kitty for a fixed weight.
+        #[weight = SimpleDispatchInfo::FixedNormal(RECOUNT_OWNED_WEIGHT)]
+        fn recount_owned(origin, account: T::AccountId) -> Result {
+            T::AdminOrigin::ensure_origin(origin)?;
+            Self::ensure_migrated()?;
+
+            let count = Self::recount_owned_kitties(&account);
+
+            Self::deposit_event(RawEvent::OwnedKittiesRecounted(account, count));
+
+            Ok(())
+        }
+
+        #[weight = SimpleDispatchInfo::FixedNormal(FAVORITE_WEIGHT)]
+        fn add_favorite(origin, kitty_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+
+            ensure!(<Kitties<T>>::exists(kitty_id), "This cat does not exist");
+            ensure!(!Self::is_favorite((sender.clone(), kitty_id)), "This cat is already a favorite");
+            let count = Self::favorite_count(&sender);
+            ensure!(count < T::MaxFavorites::get(), "Too many favorites");
+            let mut favorited_by = Self::favorited_by(kitty_id);
+            ensure!(favorited_by.len() < T::MaxFavoritesPerKitty::get() as usize, "This cat has too many favorites");
+
+            <Favorites<T>>::insert((sender.clone(), kitty_id), true);
+            <FavoriteCount<T>>::insert(&sender, count + 1);
+            favorited_by.push(sender.clone());
+            <FavoritedBy<T>>::insert(kitty_id, favorited_by);
+
+            Self::deposit_event(RawEvent::Favorited(sender, kitty_id));
+
+            Ok(())
+        }
+
+        #[weight = SimpleDispatchInfo::FixedNormal(FAVORITE_WEIGHT)]
+        fn remove_favorite(origin, kitty_id: T::Hash) -> Result {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_migrated()?;
+
+            ensure!(Self::is_favorite((sender.clone(), kitty_id)), "This cat is not a favorite");
+
+            <Favorites<T>>::remove((sender.clone(), kitty_id));
+            <FavoriteCount<T>>::mutate(&sender, |count| *count = count.saturating_sub(1));
+            <FavoritedBy<T>>::mutate(kitty_id, |accounts| accounts.retain(|account| *account != sender));
+
+            Self::deposit_event(RawEvent::Unfavorited(sender, kitty_id));
+
+            Ok(())
+        }
+
+        fn on_initialize(_n: T::BlockNumber) {
+            Self::migrate();
+            if Self::migrating() {
+                return;
+            }
+
+            // Reap before any extrinsic runs. `on_initialize` can neither return nor register
+            // weight at this revision of the runtime, so the reap is kept within a fixed
+            // share of the block instead of being charged to it.
+            let mtp = T::Time::median_time_past();
+            let budget = <T as system::Trait>::MaximumBlockWeight::get() / REAP_BUDGET_DIVISOR;
+            Self::remove_expired_kitties(mtp, budget);
+        }
+
+        fn on_finalize(_n: T::BlockNumber) {
+            CreatedThisBlock::kill();
+            if Self::migrating() {
+                return;
+            }
+            let mtp = T::Time::median_time_past();
+            Self::announce_oldness(mtp);
+            Self::expire_offers(mtp);
+            Self::expire_listings(mtp);
+        }
+
+        fn offchain_worker(_n: T::BlockNumber) {
+            if T::ReportExpiring::get() {
+                let mtp = T::Time::median_time_past();
+                let expiring = Self::expiring_kitties(mtp, T::ExpiryWarningWindow::get());
+                runtime_io::local_storage_set(StorageKind::PERSISTENT, EXPIRING_KITTIES_KEY, &expiring.encode());
+            }
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// Bring the stored kitties up to `CURRENT_STORAGE_VERSION`, at most `MIGRATION_BATCH`
+    /// kitties per call. Called every block, it is a single storage read once done.
+    /// `on_runtime_upgrade` does not exist at this Substrate revision.
+    fn migrate() {
+        let mut version = Self::storage_version();
+        if version >= CURRENT_STORAGE_VERSION {
+            return;
+        }
+
+        let count = Self::all_kitties_count();
+        let mut cursor = Self::migration_cursor();
+        let mut budget = MIGRATION_BATCH;
+        while version < CURRENT_STORAGE_VERSION && budget > 0 {
+            let end = cmp::min(cursor.saturating_add(budget), count);
+            let batch = cursor..end;
+            budget -= end - cursor;
+            match version {
+                0 => Self::migrate_to_v1(batch),
+                1 => Self::migrate_to_v2(batch),
+                2 => Self::migrate_to_v3(batch),
+                3 => Self::migrate_to_v4(batch),
+                _ => Self::migrate_to_v5(batch),
+            }
+            if end < count {
+                cursor = end;
+            } else {
+                cursor = 0;
+                version += 1;
+            }
+        }
+
+        <StorageVersion>::put(version);
+        MigrationCursor::put(cursor);
+        Migrating::put(version < CURRENT_STORAGE_VERSION);
+    }
+
+    fn ensure_migrated() -> Result {
+        ensure!(!Self::migrating(), "Kitties storage is being migrated");
+        Ok(())
+    }
+
+    /// Version 1 added `breed_count`, which starts at zero for existing kitties.
+    fn migrate_to_v1(batch: ops::Range<u64>) {
+        for index in batch {
+            let kitty_id = Self::kitty_by_index(index);
+            let key = <Kitties<T>>::hashed_key_for(&kitty_id);
+            if let Some(old) = unhashed::get::<KittyV0<T::Hash, T::Balance, T::Moment>>(&key) {
+                unhashed::put(&key, &KittyV1 {
+                    id: old.id,
+                    dna: old.dna,
+                    price: old.price,
+                    gen: old.gen,
+                    lifetime: old.lifetime,
+                    breed_count: 0,
+                });
+            }
+        }
+    }
+
+    /// Version 2 turned the zero-as-not-for-sale price into an `Option`.
+    fn migrate_to_v2(batch: ops::Range<u64>) {
+        for index in batch {
+            let kitty_id = Self::kitty_by_index(index);
+            let key = <Kitties<T>>::hashed_key_for(&kitty_id);
+            if let Some(old) = unhashed::get::<KittyV1<T::Hash, T::Balance, T::Moment>>(&key) {
+                unhashed::put(&key, &KittyV2 {
+                    id: old.id,
+                    dna: old.dna,
+                    price: if old.price.is_zero() { None } else { Some(old.price) },
+                    gen: old.gen,
+                    lifetime: old.lifetime,
+                    breed_count: old.breed_count,
+                });
+            }
+        }
+    }
+
+    /// Version 3 started tracking when kitties turn old, so queue up the live ones.
+    fn migrate_to_v3(batch: ops::Range<u64>) {
+        let mut pending = Vec::new();
+        for index in batch {
+            let kitty_id = Self::kitty_by_index(index);
+            let key = <Kitties<T>>::hashed_key_for(&kitty_id);
+            if let Some(kitty) = unhashed::get::<KittyV2<T::Hash, T::Balance, T::Moment>>(&key) {
+                pending.push(Lifespan {
+                    kitty_id,
+                    end_time: kitty.lifetime.old_time,
+                });
+            }
+        }
+        <OldnessHeap<T>>::push_vec(pending);
+    }
+
+    /// Version 4 added `last_transfer`. Existing kitties count as never transferred.
+    fn migrate_to_v4(batch: ops::Range<u64>) {
+        for index in batch {
+            let kitty_id = Self::kitty_by_index(index);
+            let key = <Kitties<T>>::hashed_key_for(&kitty_id);
+            if let Some(old) = unhashed::get::<KittyV2<T::Hash, T::Balance, T::Moment>>(&key) {
+                <Kitties<T>>::insert(kitty_id, Kitty {
+                    id: old.id,
+                    dna: old.dna,
+                    price: old.price,
+                    gen: old.gen,
+                    lifetime: old.lifetime,
+                    breed_count: old.breed_count,
+                    last_transfer: None,
+                });
+            }
+        }
+    }
+
+    /// Version 5 started indexing the kitties for sale, so index the listed ones.
+    fn migrate_to_v5(batch: ops::Range<u64>) {
+        for index in batch {
+            let kitty_id = Self::kitty_by_index(index);
+            if Self::kitty(kitty_id).price.is_some() {
+                Self::index_listing(kitty_id, true);
+            }
+        }
+    }
+
+    fn generate_lifetime(mtp: T::Moment, dna: T::Hash, gen: u64) -> result::Result<Lifetime<T::Moment>, &'static str> {
+        let birth_time = mtp.saturated_into::<u64>();
+        let young_factor = u64::from(Self::young_factor()) + u64::from(cmp::min(dna.as_ref()[0], T::MaxYoungFactor::get()));
+        let maturity_factor = u64::from(Self::maturity_factor()) + u64::from(cmp::min(dna.as_ref()[1], T::MaxMaturityFactor::get()));
+        let oldness_factor = u64::from(Self::oldness_factor()) + u64::from(cmp::min(dna.as_ref()[2], T::MaxOldnessFactor::get()));
+
+        let childhood = Self::scale_for_generation(ONE_MINUTE * young_factor, gen)
+            .ok_or("Overflow calculating the childhood for a new kitty")?;
+        let manhood = Self::scale_for_generation(ONE_DAY * maturity_factor, gen)
+            .ok_or("Overflow calculating the manhood for a new kitty")?;
+        let old_age = Self::scale_for_generation(ONE_MINUTE * oldness_factor, gen)
+            .ok_or("Overflow calculating the old age for a new kitty")?;
+
+        let maturity_time = birth_time.checked_add(childhood)
+            .ok_or("Overflow calculating the childhood for a new kitty")?;
+        let old_time = maturity_time.checked_add(manhood)
+            .ok_or("Overflow calculating the manhood for a new kitty")?;
+        let end_time = old_time.checked_add(old_age)
+            .ok_or("Overflow calculating the old age for a new kitty")?;
+
+        let lifetime = Lifetime {
+            birth_time: mtp,
+            maturity_time: maturity_time.saturated_into(),
+            old_time: old_time.saturated_into(),
+            end_time: end_time.saturated_into(),
+        };
+
+        Ok(lifetime)
+    }
+
+    /// Apply `GenerationLifetimePercent` to a life phase `gen` times. `None` on overflow.
+    fn scale_for_generation(duration: u64, gen: u64) -> Option<u64> {
+        let percent = u64::from(T::GenerationLifetimePercent::get());
+        if percent == 100 {
+            return Some(duration);
+        }
+        let mut scaled = duration;
+        let mut remaining = gen;
+        // Shrinking reaches zero and growing overflows long before `gen` runs out.
+        while remaining > 0 && scaled > 0 {
+            scaled = scaled.checked_mul(percent)? / 100;
+            remaining -= 1;
+        }
+        Some(scaled)
+    }
+
+    /// The lifetime a gen-0 `dna` would get if born at `birth`, without minting anything.
+    pub fn simulate_lifetime(dna: T::Hash, birth: T::Moment) -> result::Result<Lifetime<T::Moment>, &'static str> {
+        Self::generate_lifetime(birth, dna, 0)
+    }
+
+    fn life_stage(mtp: T::Moment, lifetime: &Lifetime<T::Moment>) -> LifeStage {
+        LifeStage::from_times(mtp, lifetime)
+    }
+
+    /// `BreedFeeBase` plus `BreedFeeStep` for each time either parent has bred before.
+    pub fn breed_fee(kitty_1: &Kitty<T::Hash, T::Balance, T::Moment>, kitty_2: &Kitty<T::Hash, T::Balance, T::Moment>)
+        -> T::Balance
+    {
+        let breeds = kitty_1.breed_count.saturating_add(kitty_2.breed_count);
+        let breeds = T::Balance::from(breeds.saturated_into::<u32>());
+        T::BreedFeeBase::get().saturating_add(T::BreedFeeStep::get().saturating_mul(breeds))
+    }
+
+    /// The breeding window of `who` as seen at `mtp`, restarted once a full day has passed.
+    fn current_breed_window(mtp: T::Moment, who: &T::AccountId) -> (T::Moment, u32) {
+        let (window_start, bred) = Self::breed_window(who);
+        if mtp >= window_start.saturating_add(ONE_DAY.saturated_into()) {
+            (mtp, 0)
+        } else {
+            (window_start, bred)
+        }
+    }
+
+    /// Whether the kitty can breed at `mtp`, i.e. is within its `BreedingWindow`.
+    pub fn could_breed(mtp: T::Moment, kitty: &Kitty<T::Hash, T::Balance, T::Moment>) -> bool {
+        let start = T::BreedingWindow::breeding_start(&kitty.lifetime);
+        let end = cmp::min(T::BreedingWindow::breeding_end(&kitty.lifetime), kitty.lifetime.end_time);
+        mtp >= start && mtp < end
+    }
+
+    /// Whether the kitty is young, mature or old at `mtp`. A kitty past its `end_time`
+    /// stays in storage until reaped, but is dead and must not be traded.
+    pub fn is_alive(mtp: T::Moment, kitty: &Kitty<T::Hash, T::Balance, T::Moment>) -> bool {
+        Self::life_stage(mtp, &kitty.lifetime) != LifeStage::Invalid
+    }
+
+    /// Whether the kitty can change hands or be listed at `mtp`, i.e. is young or mature.
+    pub fn could_transfer(mtp: T::Moment, kitty: &Kitty<T::Hash, T::Balance, T::Moment>) -> bool {
+        match Self::life_stage(mtp, &kitty.lifetime) {
+            LifeStage::Young => true,
+            LifeStage::Maturity => true,
+            _ => false
+        }
+    }
+
+    /// What the kitty can do at the current median time past, as
+    /// `(can_breed, can_transfer, can_list)`. All false if the kitty does not exist.
+    /// Soulbound and encumbered kitties can't be transferred or listed.
+    pub fn capabilities(kitty_id: T::Hash) -> (bool, bool, bool) {
+        if !<Kitties<T>>::exists(kitty_id) || Self::is_frozen(kitty_id) {
+            return (false, false, false);
+        }
+        let mtp = T::Time::median_time_past();
+        let kitty = Self::kitty(kitty_id);
+        let could_transfer = Self::could_transfer(mtp, &kitty)
+            && !Self::is_soulbound(kitty_id) && !Self::is_encumbered(kitty_id);
+        (
+            Self::could_breed(mtp, &kitty),
+            could_transfer && Self::past_transfer_cooldown(mtp, &kitty),
+            could_transfer && Self::past_sale_grace_period(mtp, &kitty),
+        )
+    }
+
+    /// Whether `SaleGracePeriod` has passed since the kitty was born.
+    fn past_sale_grace_period(mtp: T::Moment, kitty: &Kitty<T::Hash, T::Balance, T::Moment>) -> bool {
+        mtp >= kitty.lifetime.birth_time.saturating_add(T::SaleGracePeriod::get())
+    }
+
+    /// Whether another module holds the kitty for a pending operation, blocking
+    /// transfers, sales and new listings until it completes or is cancelled.
+    pub fn is_encumbered(kitty_id: T::Hash) -> bool {
+        T::Encumbrances::is_encumbered(&kitty_id)
+    }
+
+    /// Whether `TransferCooldown` has passed since the kitty last changed hands.
+    fn past_transfer_cooldown(mtp: T::Moment, kitty: &Kitty<T::Hash, T::Balance, T::Moment>) -> bool {
+        match kitty.last_transfer {
+            Some(last_transfer) => mtp >= last_transfer.saturating_add(T::TransferCooldown::get()),
+            None => true,
+        }
+    }
+
+    /// Time left until the kitty is mature, zero once it is. `None` if the kitty does not exist.
+    pub fn time_to_maturity(kitty_id: T::Hash) -> Option<T::Moment> {
+        Self::time_until(kitty_id, |lifetime| lifetime.maturity_time)
+    }
+
+    /// Time left until the kitty dies, zero once it has. `None` if the kitty does not exist.
+    pub fn time_to_death(kitty_id: T::Hash) -> Option<T::Moment> {
+        Self::time_until(kitty_id, |lifetime| lifetime.end_time)
+    }
+
+    /// The kitty, its owner and its current state in one read. `None` if it does not exist.
+    pub fn kitty_details(kitty_id: T::Hash) -> Option<KittyDetails<T::Hash, T::Balance, T::Moment, T::AccountId>> {
+        let owner = Self::owner_of(kitty_id)?;
+        let kitty = Self::kitty(kitty_id);
+        let mtp = T::Time::median_time_past();
+        Some(KittyDetails {
+            life_stage: Self::life_stage(mtp, &kitty.lifetime),
+            time_to_death: kitty.lifetime.end_time.saturating_sub(mtp),
+            name: Self::name_of(kitty_id),
+            kitty,
+            owner,
+        })
+    }
+
+    fn time_until<F: Fn(&Lifetime<T::Moment>) -> T::Moment>(kitty_id: T::Hash, moment: F) -> Option<T::Moment> {
+        if !<Kitties<T>>::exists(kitty_id) {
+            return None;
+        }
+        let mtp = T::Time::median_time_past();
+        Some(moment(&Self::kitty(kitty_id).lifetime).saturating_sub(mtp))
+    }
+
+    /// Level of a kitty, i.e. how many of `LEVEL_THRESHOLDS` its experience has reached.
+    pub fn level(kitty_id: T::Hash) -> u32 {
+        Self::level_for(Self::experience_of(kitty_id))
+    }
+
+    fn level_for(experience: u32) -> u32 {
+        LEVEL_THRESHOLDS.iter().filter(|threshold| experience >= **threshold).count() as u32
+    }
+
+    /// Award one experience point, announcing the new level if a threshold was crossed.
+    fn gain_experience(kitty_id: T::Hash) {
+        let experience = Self::experience_of(kitty_id);
+        let new_experience = experience.saturating_add(1);
+        <Experience<T>>::insert(kitty_id, new_experience);
+
+        let new_level = Self::level_for(new_experience);
+        if new_level > Self::level_for(experience) {
+            Self::deposit_event(RawEvent::LeveledUp(kitty_id, new_level));
+        }
+    }
+
+    /// The form a name is compared in: surrounding ASCII whitespace trimmed and ASCII lowercased.
+    fn normalize_name(name: &[u8]) -> Vec<u8> {
+        let start = name.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(name.len());
+        let end = name.iter().rposition(|b| !b.is_ascii_whitespace()).map_or(start, |i| i + 1);
+        name[start..end].iter().map(|b| b.to_ascii_lowercase()).collect()
+    }
+
+    /// Drop the kitty's name, making it available to other kitties.
+    fn release_name(kitty_id: T::Hash) {
+        let name = <Names<T>>::take(kitty_id);
+        if !name.is_empty() {
+            <NameOwner<T>>::remove(Self::normalize_name(&name));
+        }
+    }
+
+    /// Decode the visible traits of `dna`. Bytes 0 to 2 drive the lifetime, so
+    /// traits start at byte 3.
+    pub fn phenotype(dna: &T::Hash) -> Phenotype {
+        let dna = dna.as_ref();
+        Phenotype {
+            body_color: [dna[3], dna[4], dna[5]],
+            pattern: dna[6] % PATTERN_COUNT,
+            eye_shape: dna[7] % EYE_SHAPE_COUNT,
+        }
+    }
+
+    /// `phenotype` packed in a byte: the pattern in bits 0 to 2, the eye shape in bits
+    /// 3 and 4, and whether each of red, green and blue is bright (128 or more) in bits 5 to 7.
+    pub fn dna_summary(dna: &T::Hash) -> u8 {
+        let phenotype = Self::phenotype(dna);
+        let bright = |channel: u8| channel >> 7;
+        phenotype.pattern
+            | phenotype.eye_shape << 3
+            | bright(phenotype.body_color[0]) << 5
+            | bright(phenotype.body_color[1]) << 6
+            | bright(phenotype.body_color[2]) << 7
+    }
+
+    /// Id of the first of `FancyPatterns` that `dna` matches, if any.
+    pub fn fancy_kind(dna: &T::Hash) -> Option<u32> {
+        T::FancyPatterns::get().into_iter()
+            .find(|pattern| dna.as_ref().starts_with(&pattern.prefix))
+            .map(|pattern| pattern.id)
+    }
+
+    /// Split of `price` taken by the marketplace. Never exceeds `price`.
+    fn marketplace_fee(price: T::Balance) -> T::Balance {
+        cmp::min(T::MarketplaceFee::get() * price, price)
+    }
+
+    /// Split of `price` paid to a referrer. Never exceeds what is left after the marketplace fee.
+    fn referral_reward(price: T::Balance) -> T::Balance {
+        cmp::min(T::ReferralPercent::get() * price, price - Self::marketplace_fee(price))
+    }
+
+    /// Withdraw `price` from `buyer` in one go, then hand the marketplace fee to
+    /// `FeeDestination`, the referral reward to `referrer` and the rest to `seller`,
+    /// so a sale never moves funds partially.
+    fn pay_for_kitty(buyer: &T::AccountId, seller: &T::AccountId, price: T::Balance,
+        referrer: Option<&T::AccountId>) -> Result
+    {
+        let payment = <balances::Module<T> as Currency<_>>::withdraw(
+            buyer, price, WithdrawReason::Transfer, ExistenceRequirement::KeepAlive)?;
+        Self::distribute_payment(seller, payment, price, referrer);
+
+        Ok(())
+    }
+
+    /// Split a `price` already withdrawn from the buyer between the fee pot, the
+    /// referrer and the seller.
+    fn distribute_payment(seller: &T::AccountId, payment: NegativeImbalanceOf<T>, price: T::Balance,
+        referrer: Option<&T::AccountId>)
+    {
+        let (fee, mut proceeds) = payment.split(Self::marketplace_fee(price));
+
+        <balances::Module<T> as Currency<_>>::resolve_creating(&T::FeeDestination::get(), fee);
+        if let Some(referrer) = referrer {
+            let (reward, rest) = proceeds.split(Self::referral_reward(price));
+            <balances::Module<T> as Currency<_>>::resolve_creating(referrer, reward);
+            proceeds = rest;
+        }
+        <balances::Module<T> as Currency<_>>::resolve_creating(seller, proceeds);
+    }
+
+    /// Everything that must hold for `buyer` to buy `kitty_id` for at most `max_price`,
+    /// short of payment and room to receive it. Returns the seller and the price.
+    fn check_purchase(buyer: &T::AccountId, kitty_id: T::Hash, max_price: T::Balance)
+        -> result::Result<(T::AccountId, T::Balance), &'static str>
+    {
+        ensure!(<Kitties<T>>::exists(kitty_id), "This cat does not exist");
+
+        let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
+        ensure!(owner != *buyer, "You can't buy your own cat");
+        ensure!(!Self::is_frozen(kitty_id), "This cat is frozen");
+        ensure!(!Self::is_encumbered(kitty_id), "This cat has a pending operation");
+        ensure!(!Self::is_soulbound(kitty_id), "This cat is soulbound");
+
+        let mtp = T::Time::median_time_past();
+        let kitty = Self::kitty(kitty_id);
+        ensure!(Self::is_alive(mtp, &kitty), "This cat is no longer alive");
+        ensure!(Self::could_transfer(mtp, &kitty), "This cat is not in the life stage that can be transferred");
+        let kitty_price = kitty.price.ok_or("The cat you want to buy is not for sale")?;
+        ensure!(kitty_price <= max_price, "The cat you want to buy costs more than your max price");
+        ensure!(Self::past_transfer_cooldown(mtp, &kitty), "This cat changed hands too recently");
+
+        Ok((owner, kitty_price))
+    }
+
+    /// Hand a paid-for kitty to its buyer and take it off the market.
+    fn complete_sale(buyer: T::AccountId, seller: T::AccountId, kitty_id: T::Hash, price: T::Balance, memo: Vec<u8>) {
+        Self::transfer_from(seller.clone(), buyer.clone(), kitty_id, memo.clone())
+            .expect("`seller` is shown to own the kitty; \
+            `seller` must have greater than 0 kitties, so transfer cannot cause underflow; \
+            `all_kitty_count` shares the same type as `owned_kitty_count` \
+            and minting ensure there won't ever be more than `max()` kitties, \
+            which means transfer cannot cause an overflow; \
+            qed");
+
+        <Kitties<T>>::mutate(kitty_id, |kitty| kitty.price = None);
+        Self::index_listing(kitty_id, false);
+
+        Self::deposit_event(RawEvent::Bought(buyer, seller, kitty_id, price, memo));
+    }
+
+    /// How a `price` paid in a non-native asset is split: the fee, the referral
+    /// reward and what is left for the seller.
+    fn split_asset_payment(price: T::Balance, referrer: Option<&T::AccountId>)
+        -> (T::Balance, T::Balance, T::Balance)
+    {
+        let fee = Self::marketplace_fee(price);
+        let reward = if referrer.is_some() { Self::referral_reward(price) } else { Zero::zero() };
+        (fee, reward, price - fee - reward)
+    }
+
+    /// Everything `pay_in_asset` needs to succeed: enough balance for the whole price
+    /// and every leg of the split accepted by `T::Assets`.
+    fn check_asset_payment(asset_id: T::AssetId, buyer: &T::AccountId, seller: &T::AccountId, price: T::Balance,
+        referrer: Option<&T::AccountId>) -> Result
+    {
+        ensure!(T::Assets::free_balance(asset_id, buyer) >= price, "Not enough balance in the price asset");
+
+        let (fee, reward, proceeds) = Self::split_asset_payment(price, referrer);
+        T::Assets::can_transfer(asset_id, buyer, &T::FeeDestination::get(), fee)?;
+        if let Some(referrer) = referrer {
+            T::Assets::can_transfer(asset_id, buyer, referrer, reward)?;
+        }
+        T::Assets::can_transfer(asset_id, buyer, seller, proceeds)
+    }
+
+    /// `pay_for_kitty` for a kitty priced in `asset_id`. Every transfer is checked before
+    /// the first one is made, so a failure leaves all balances untouched unless `T::Assets`
+    /// rejects a transfer it has just accepted in `can_transfer`.
+    fn pay_in_asset(asset_id: T::AssetId, buyer: &T::AccountId, seller: &T::AccountId, price: T::Balance,
+        referrer: Option<&T::AccountId>) -> Result
+    {
+        Self::check_asset_payment(asset_id, buyer, seller, price, referrer)?;
+
+        let (fee, reward, proceeds) = Self::split_asset_payment(price, referrer);
+        T::Assets::transfer(asset_id, buyer, &T::FeeDestination::get(), fee)?;
+        if let Some(referrer) = referrer {
+            T::Assets::transfer(asset_id, buyer, referrer, reward)?;
+        }
+        T::Assets::transfer(asset_id, buyer, seller, proceeds)
+    }
+
+    /// Whether `who` has room for one more kitty under `MaxKittiesOwned`.
+    fn ensure_can_receive(who: &T::AccountId) -> Result {
+        ensure!(Self::live_kitty_count(who) < T::MaxKittiesOwned::get(), "The recipient owns too many kitties");
+        Ok(())
+    }
+
+    /// The kitties of `who` that count toward `MaxKittiesOwned`, i.e. all but the frozen ones.
+    fn live_kitty_count(who: &T::AccountId) -> u64 {
+        Self::owned_kitty_count(who).saturating_sub(Self::frozen_kitty_count(who))
+    }
+
+    /// `create_kitty` for `sender`, returning the id of the new kitty.
+    pub fn do_create(sender: T::AccountId) -> result::Result<T::Hash, &'static str> {
+        ensure!(!T::CreatorGating::get() || Self::is_creator(&sender), "This account may not create kitties");
+        ensure!(<balances::Module<T>>::free_balance(&sender) >= T::MinBalanceToCreate::get(),
+            "Not enough free balance to create a kitty");
+
+        Self::create_gen0(sender)
+    }
+
+    /// Mint a new gen-0 kitty with random DNA to `owner`.
+    fn create_gen0(owner: T::AccountId) -> result::Result<T::Hash, &'static str> {
+        let nonce = <Nonce>::get();
+        // The block number and in-block counter keep ids distinct even if the seed
+        // and nonce ever repeat.
+        let block_number = <system::Module<T>>::block_number();
+        let created_this_block = CreatedThisBlock::get();
+        let random_hash = (<system::Module<T>>::random_seed(), &owner, nonce, block_number, created_this_block)
+            .using_encoded(<T as system::Trait>::Hashing::hash);
+        let dna = T::DnaGenerator::generate(random_hash);
+
+        let mtp = T::Time::median_time_past();
+        let new_kitty = Kitty {
+            id: random_hash,
+            dna,
+            price: None,
+            gen: 0,
+            lifetime: Self::generate_lifetime(mtp, dna, 0)?,
+            breed_count: 0,
+            last_transfer: None,
+        };
+
+        Self::mint(owner, random_hash, new_kitty)?;
+
+        <Nonce>::mutate(|n| *n += 1);
+        CreatedThisBlock::mutate(|n| *n = n.saturating_add(1));
+
+        Ok(random_hash)
+    }
+
+    fn mint(to: T::AccountId, kitty_id: T::Hash, new_kitty: Kitty<T::Hash, T::Balance, T::Moment>) -> Result {
+        ensure!(!<KittyOwner<T>>::exists(kitty_id), "Kitty already exists");
+        Self::ensure_can_receive(&to)?;
+
+        let owned_kitty_count = Self::owned_kitty_count(&to);
+
+        let new_owned_kitty_count = owned_kitty_count.checked_add(1)
+            .ok_or("Overflow adding a new kitty to account balance")?;
+
+        let all_kitties_count = Self::all_kitties_count();
+
+        let new_all_kitties_count = all_kitties_count.checked_add(1)
+            .ok_or("Overflow adding a new kitty to total supply")?;
+
+        <Kitties<T>>::insert(kitty_id, &new_kitty);
+        <KittyOwner<T>>::insert(kitty_id, &to);
+
+        <AllKittiesArray<T>>::insert(all_kitties_count, kitty_id);
+        <AllKittiesCount>::put(new_all_kitties_count);
+        <AllKittiesIndex<T>>::insert(kitty_id, all_kitties_count);
+        <TotalEverCreated>::mutate(|n| *n = n.saturating_add(1));
+
+        <OwnedKittiesArray<T>>::insert((to.clone(), owned_kitty_count), kitty_id);
+        <OwnedKittiesCount<T>>::insert(&to, new_owned_kitty_count);
+        <OwnedKittiesIndex<T>>::insert(kitty_id, owned_kitty_count);
+        Self::record_owner(kitty_id, to.clone());
+
+        <LifespanHeap<T>>::push(Lifespan {
+            kitty_id,
+            end_time: new_kitty.lifetime.end_time,
+        });
+        <OldnessHeap<T>>::push(Lifespan {
+            kitty_id,
+            end_time: new_kitty.lifetime.old_time,
+        });
+
+        T::OnCreated::on_created(&to, &kitty_id);
+
+        Self::deposit_event(RawEvent::Created(to, kitty_id));
+        if T::EmitDnaSummary::get() {
+            Self::deposit_event(RawEvent::DnaSummary(kitty_id, Self::dna_summary(&new_kitty.dna)));
+        }
+
+        if let Some(fancy_id) = Self::fancy_kind(&new_kitty.dna) {
+            <Fancy<T>>::insert(kitty_id, fancy_id);
+            Self::deposit_event(RawEvent::FancyMinted(kitty_id, fancy_id));
+        }
+
+        Ok(())
+    }
+
+    /// The checks every owner-initiated move of a kitty goes through, ownership aside.
+    fn ensure_tradable(mtp: T::Moment, kitty_id: T::Hash) -> Result {
+        ensure!(!Self::is_frozen(kitty_id), "This cat is frozen");
+        ensure!(!Self::is_encumbered(kitty_id), "This cat has a pending operation");
+        ensure!(!Self::is_soulbound(kitty_id), "This cat is soulbound");
+        let kitty = Self::kitty(kitty_id);
+        ensure!(Self::could_transfer(mtp, &kitty),
+            "This cat is not in the life stage that can be transferred");
+        ensure!(Self::past_transfer_cooldown(mtp, &kitty), "This cat changed hands too recently");
+        Ok(())
+    }
+
+    fn ensure_memo(memo: &[u8]) -> Result {
+        ensure!(memo.len() <= T::MaxMemoLength::get() as usize, "The memo is too long");
+        Ok(())
+    }
+
+    fn transfer_from(from: T::AccountId, to: T::AccountId, kitty_id: T::Hash, memo: Vec<u8>) -> Result {
+        // The swap-remove below would corrupt the owned-kitties index if both sides were the same.
+        ensure!(from != to, "Cannot transfer a kitty to its owner");
+        let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
+
+        ensure!(owner == from, "'from' account does not own this kitty");
+        Self::ensure_can_receive(&to)?;
+
+        Self::move_kitty(from, to, kitty_id, memo)
+    }
+
+    /// The bookkeeping of `transfer_from` without its checks. `from` must own the kitty;
+    /// `MaxKittiesOwned` is not enforced.
+    fn move_kitty(from: T::AccountId, to: T::AccountId, kitty_id: T::Hash, memo: Vec<u8>) -> Result {
+        let owned_kitty_count_from = Self::owned_kitty_count(&from);
+        let owned_kitty_count_to = Self::owned_kitty_count(&to);
+
+        let new_owned_kitty_count_to = owned_kitty_count_to.checked_add(1)
+            .ok_or("Transfer causes overflow of 'to' kitty balance")?;
 
         let new_owned_kitty_count_from = owned_kitty_count_from.checked_sub(1)
             .ok_or("Transfer causes underflow of 'from' kitty balance")?;
 
-        let kitty_index = <OwnedKittiesIndex<T>>::get(kitty_id);
-        if kitty_index != new_owned_kitty_count_from {
-            let last_kitty_id = <OwnedKittiesArray<T>>::get((from.clone(), new_owned_kitty_count_from));
-            <OwnedKittiesArray<T>>::insert((from.clone(), kitty_index), last_kitty_id);
-            <OwnedKittiesIndex<T>>::insert(last_kitty_id, kitty_index);
-        }
+        // Moving the last owned kitty, the common case, skips the swap and its two
+        // writes; only the removal of the tail entry below remains.
+        let kitty_index = <OwnedKittiesIndex<T>>::get(kitty_id);
+        if kitty_index != new_owned_kitty_count_from {
+            let last_kitty_id = <OwnedKittiesArray<T>>::get((from.clone(), new_owned_kitty_count_from));
+            <OwnedKittiesArray<T>>::insert((from.clone(), kitty_index), last_kitty_id);
+            <OwnedKittiesIndex<T>>::insert(last_kitty_id, kitty_index);
+        }
+
+        <KittyOwner<T>>::insert(&kitty_id, &to);
+        <OwnedKittiesIndex<T>>::insert(kitty_id, owned_kitty_count_to);
+
+        <OwnedKittiesArray<T>>::remove((from.clone(), new_owned_kitty_count_from));
+        <OwnedKittiesArray<T>>::insert((to.clone(), owned_kitty_count_to), kitty_id);
+
+        <OwnedKittiesCount<T>>::insert(&from, new_owned_kitty_count_from);
+        <OwnedKittiesCount<T>>::insert(&to, new_owned_kitty_count_to);
+        if Self::is_frozen(kitty_id) {
+            <FrozenKittyCount<T>>::mutate(&from, |count| *count = count.saturating_sub(1));
+            <FrozenKittyCount<T>>::mutate(&to, |count| *count = count.saturating_add(1));
+        }
+
+        // A listing belongs to the owner who made it, the new owner has to relist.
+        let mtp = T::Time::median_time_past();
+        <Kitties<T>>::mutate(kitty_id, |kitty| {
+            kitty.price = None;
+            kitty.last_transfer = Some(mtp);
+        });
+        Self::index_listing(kitty_id, false);
+        // Nobody can buy their own kitty, so an offer the new owner made on it is refunded.
+        if let Some(offer) = Self::take_offer(kitty_id, |offer| offer.buyer == to) {
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&to, offer.amount);
+            Self::deposit_event(RawEvent::OfferCancelled(to.clone(), kitty_id));
+        }
+        Self::record_owner(kitty_id, to.clone());
+        Self::gain_experience(kitty_id);
+
+        T::OnTransferred::on_transferred(&from, &to, &kitty_id);
+
+        Self::deposit_event(RawEvent::Transferred(from, to, kitty_id, memo));
+
+        Ok(())
+    }
+
+    /// Append `owner` to the kitty's history, dropping the oldest entries past `MaxOwnerHistory`.
+    fn record_owner(kitty_id: T::Hash, owner: T::AccountId) {
+        let max = T::MaxOwnerHistory::get() as usize;
+        <OwnerHistory<T>>::mutate(kitty_id, |history| {
+            history.push(owner);
+            if history.len() > max {
+                let excess = history.len() - max;
+                history.drain(..excess);
+            }
+        });
+    }
+
+    /// Set the price of a kitty `sender` owns and can trade, keeping the for-sale index in step.
+    fn update_price(sender: &T::AccountId, kitty_id: T::Hash, new_price: Option<T::Balance>) -> Result {
+        ensure!(<Kitties<T>>::exists(kitty_id), "This cat does not exist");
+
+        let owner = Self::owner_of(kitty_id).ok_or("No owner for this kitty")?;
+        ensure!(owner == *sender, "You do not own this cat");
+        ensure!(new_price.is_none() || !Self::is_encumbered(kitty_id), "This cat has a pending operation");
+        ensure!(new_price.is_none() || !Self::is_soulbound(kitty_id), "This cat is soulbound");
+
+        let mtp = T::Time::median_time_past();
+        <Kitties<T>>::mutate(kitty_id, |kitty| -> Result {
+            ensure!(new_price.is_none() || Self::could_transfer(mtp, kitty),
+                "This cat is not in the life stage that can be transferred");
+            ensure!(new_price.is_none() || Self::past_sale_grace_period(mtp, kitty),
+                "This cat is too young to be listed");
+            kitty.price = new_price;
+            Ok(())
+        })?;
+        Self::index_listing(kitty_id, new_price.is_some());
+
+        let ttl = T::ListingTtl::get();
+        if new_price.is_some() && !ttl.is_zero() {
+            let expires_at = mtp.saturating_add(ttl);
+            <ListingExpiresAt<T>>::insert(kitty_id, expires_at);
+            // Drop the entry of an earlier listing, so relisting can't grow the heap.
+            <ListingExpiryHeap<T>>::retain(|expiry| expiry.kitty_id != kitty_id);
+            <ListingExpiryHeap<T>>::push(Lifespan { kitty_id, end_time: expires_at });
+        }
+
+        Ok(())
+    }
+
+    /// Add the kitty to or remove it from the for-sale index. A no-op if it is already
+    /// in the requested state. Delisting also forgets the asset it was priced in.
+    fn index_listing(kitty_id: T::Hash, listed: bool) {
+        if !listed {
+            <PriceAsset<T>>::remove(kitty_id);
+            <ListingExpiresAt<T>>::remove(kitty_id);
+        }
+        let indexed = <ForSaleIndex<T>>::exists(kitty_id);
+        if listed && !indexed {
+            let count = Self::for_sale_count();
+            <ForSaleArray<T>>::insert(count, kitty_id);
+            <ForSaleIndex<T>>::insert(kitty_id, count);
+            ForSaleCount::put(count + 1);
+        } else if !listed && indexed {
+            // Saturating so a count out of step with the index can't wrap around.
+            let last_index = Self::for_sale_count().saturating_sub(1);
+            let kitty_index = <ForSaleIndex<T>>::take(kitty_id);
+            if kitty_index != last_index {
+                let last_kitty_id = Self::for_sale_by_index(last_index);
+                <ForSaleArray<T>>::insert(kitty_index, last_kitty_id);
+                <ForSaleIndex<T>>::insert(last_kitty_id, kitty_index);
+            }
+            <ForSaleArray<T>>::remove(last_index);
+            ForSaleCount::put(last_index);
+        }
+    }
+
+    /// Kitties for sale priced within `min..=max`. Walks every listing, so it is
+    /// O(listings) and meant for RPC queries only.
+    pub fn for_sale_in_range(min: T::Balance, max: T::Balance) -> Vec<T::Hash> {
+        (0..Self::for_sale_count())
+            .map(Self::for_sale_by_index)
+            .filter(|kitty_id| match Self::kitty(kitty_id).price {
+                Some(price) => min <= price && price <= max,
+                None => false,
+            })
+            .collect()
+    }
+
+    /// Remove and return the first offer on `kitty_id` matching `pred`. Does not touch balances.
+    fn take_offer<F: Fn(&Offer<T::AccountId, T::Balance, T::Moment>) -> bool>(kitty_id: T::Hash, pred: F)
+        -> Option<Offer<T::AccountId, T::Balance, T::Moment>>
+    {
+        let mut offers = Self::offers_for(kitty_id);
+        let position = offers.iter().position(pred)?;
+        let offer = offers.remove(position);
+        if offers.is_empty() {
+            <Offers<T>>::remove(kitty_id);
+        } else {
+            <Offers<T>>::insert(kitty_id, offers);
+        }
+        Some(offer)
+    }
+
+    /// Drop every offer on `kitty_id` and unreserve the bids. Returns how many there were.
+    fn release_offers(kitty_id: T::Hash) -> u32 {
+        let offers = <Offers<T>>::take(kitty_id);
+        for offer in &offers {
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&offer.buyer, offer.amount);
+        }
+        offers.len() as u32
+    }
+
+    /// Remove a swap proposal and its index entries, and return the proposer's deposit.
+    fn close_swap(proposal_id: u64, proposal: &SwapProposal<T::AccountId, T::Hash, T::Balance>) {
+        <SwapProposals<T>>::remove(proposal_id);
+        for kitty_id in &[proposal.proposer_kitty, proposal.counterparty_kitty] {
+            <SwapsByKitty<T>>::mutate(kitty_id, |ids| ids.retain(|id| *id != proposal_id));
+        }
+        <balances::Module<T> as ReservableCurrency<_>>::unreserve(&proposal.proposer, proposal.deposit);
+    }
+
+    /// Cancel every open swap involving `kitty_id`, which can no longer be traded.
+    /// Returns how many were cancelled.
+    fn release_swaps(kitty_id: T::Hash) -> u32 {
+        let ids = <SwapsByKitty<T>>::take(kitty_id);
+        for &proposal_id in &ids {
+            if let Some(proposal) = Self::swap_proposal(proposal_id) {
+                Self::close_swap(proposal_id, &proposal);
+                Self::deposit_event(RawEvent::SwapCancelled(proposal_id));
+            }
+        }
+        ids.len() as u32
+    }
+
+    /// Take down the listings whose time to live ended by `mtp`.
+    fn expire_listings(mtp: T::Moment) {
+        for expiry in <ListingExpiryHeap<T>>::pop_while(|expiry| expiry.end_time <= mtp) {
+            if Self::listing_expires_at(expiry.kitty_id) == Some(expiry.end_time) {
+                <Kitties<T>>::mutate(expiry.kitty_id, |kitty| kitty.price = None);
+                Self::index_listing(expiry.kitty_id, false);
+                Self::deposit_event(RawEvent::ListingExpired(expiry.kitty_id));
+            }
+        }
+    }
+
+    /// Lapse the offers whose time to live ended by `mtp`, releasing the bids.
+    fn expire_offers(mtp: T::Moment) {
+        for expiry in <OfferExpiryHeap<T>>::pop_while(|expiry| expiry.expires_at <= mtp) {
+            let offer = Self::take_offer(expiry.kitty_id, |offer| {
+                offer.buyer == expiry.buyer && offer.expires_at == expiry.expires_at
+            });
+            if let Some(offer) = offer {
+                <balances::Module<T> as ReservableCurrency<_>>::unreserve(&offer.buyer, offer.amount);
+                Self::deposit_event(RawEvent::OfferExpired(offer.buyer, expiry.kitty_id));
+            }
+        }
+    }
+
+    /// Rebuild `LifespanHeap` from the kitties that are alive, i.e. not frozen.
+    /// Returns how many entries the heap holds afterwards.
+    fn rebuild_lifespans() -> u64 {
+        let lifespans: Vec<_> = (0..Self::all_kitties_count())
+            .map(Self::kitty_by_index)
+            .filter(|kitty_id| !Self::is_frozen(kitty_id))
+            .map(|kitty_id| Lifespan {
+                kitty_id,
+                end_time: Self::kitty(kitty_id).lifetime.end_time,
+            })
+            .collect();
+        let rebuilt = lifespans.len() as u64;
+        <LifespanHeap<T>>::from_vec(lifespans);
+        rebuilt
+    }
+
+    /// Replace `account`'s entries in the owned-kitties arrays with the kitties
+    /// `KittyOwner` says it owns. Returns how many that is.
+    fn reindex_owned_kitties(account: &T::AccountId) -> u64 {
+        let owned = Self::owned_per_kitty_owner(account);
+
+        // The stored count can be too low as well as too high, so clear past both it and
+        // the new count, up to the first index with nothing stored.
+        let clear_to = cmp::max(Self::owned_kitty_count(account), owned.len() as u64);
+        let mut index = 0;
+        while index < clear_to || <OwnedKittiesArray<T>>::exists((account.clone(), index)) {
+            <OwnedKittiesArray<T>>::remove((account.clone(), index));
+            index += 1;
+        }
+        for (index, kitty_id) in owned.iter().enumerate() {
+            <OwnedKittiesArray<T>>::insert((account.clone(), index as u64), kitty_id);
+            <OwnedKittiesIndex<T>>::insert(kitty_id, index as u64);
+        }
+        let count = owned.len() as u64;
+        <OwnedKittiesCount<T>>::insert(account, count);
+        count
+    }
+
+    /// Set `account`'s `OwnedKittiesCount` to the number of kitties `KittyOwner` says
+    /// it owns, and return it. The arrays are left alone, see `reindex_owned_kitties`.
+    pub fn recount_owned_kitties(account: &T::AccountId) -> u64 {
+        let count = Self::owned_per_kitty_owner(account).len() as u64;
+        if count != Self::owned_kitty_count(account) {
+            runtime_io::print("recount_owned_kitties(): Stored count diverged, correcting it");
+            <OwnedKittiesCount<T>>::insert(account, count);
+        }
+        count
+    }
+
+    /// Every live kitty `KittyOwner` attributes to `account`, in `AllKittiesArray` order.
+    fn owned_per_kitty_owner(account: &T::AccountId) -> Vec<T::Hash> {
+        (0..Self::all_kitties_count())
+            .map(Self::kitty_by_index)
+            .filter(|kitty_id| Self::owner_of(kitty_id).as_ref() == Some(account))
+            .collect()
+    }
+
+    /// Ids of the kitties whose end time falls before `now + window`. Does not touch storage.
+    fn expiring_kitties(now: T::Moment, window: T::Moment) -> Vec<T::Hash> {
+        let limit = now.saturating_add(window);
+        <LifespanHeap<T>>::to_vec().into_iter()
+            .filter(|lifespan| lifespan.end_time < limit)
+            .map(|lifespan| lifespan.kitty_id)
+            .collect()
+    }
+
+    /// Emit `EnteringOldness` for every kitty whose maturity ended by `mtp`, matching
+    /// `life_stage`, which counts `old_time` itself as old.
+    fn announce_oldness(mtp: T::Moment) {
+        for lifespan in <OldnessHeap<T>>::pop_while(|lifespan| lifespan.end_time <= mtp) {
+            if <Kitties<T>>::exists(lifespan.kitty_id) {
+                Self::deposit_event(RawEvent::EnteringOldness(lifespan.kitty_id));
+            }
+        }
+    }
+
+    /// Weight of reaping one kitty off a lifespan heap of `heap_len`.
+    fn reap_weight_for(heap_len: u32) -> Weight {
+        T::WeightInfo::burn_one().saturating_add(T::WeightInfo::heap_pop(heap_len))
+    }
+
+    /// Reap the kitties whose lifespan ended before `mtp`, stopping before the weight of
+    /// the reap would exceed `budget`. Returns the weight used.
+    fn remove_expired_kitties(mtp: T::Moment, budget: Weight) -> Weight {
+        let stake = Lifespan {
+            kitty_id: T::Hash::default(),
+            end_time: mtp,
+        };
+        let mut weight: Weight = 0;
+        let (expired_kitties, remaining) = <LifespanHeap<T>>::pop_vec_within(&stake, |heap_len| {
+            let next = weight.saturating_add(Self::reap_weight_for(heap_len as u32));
+            if next > budget {
+                return false;
+            }
+            weight = next;
+            true
+        });
+        let mode = T::ExpiryBehavior::get();
+        let archive = T::ExpiryDestination::get();
+        let reaped = expired_kitties.len() as u32;
+        let batch = T::ExpiryEvents::get() == ExpiryEvents::Batch;
+        let mut reaped_ids = Vec::new();
+        for lifespan in expired_kitties {
+            // Frozen kitties can't be sold either, so bidders get their funds back in both modes.
+            let released = Self::release_offers(lifespan.kitty_id);
+            if released > 0 {
+                Self::deposit_event(RawEvent::OffersAbortedByExpiry(lifespan.kitty_id, released));
+            }
+            Self::release_swaps(lifespan.kitty_id);
+            match (&archive, mode) {
+                (Some(archive), _) => Self::retire_token(lifespan.kitty_id, archive.clone()),
+                (None, ExpiryMode::Burn) => Self::burn_token(lifespan.kitty_id),
+                (None, ExpiryMode::Freeze) => Self::freeze_token(lifespan.kitty_id),
+            }
+            if batch {
+                reaped_ids.push(lifespan.kitty_id);
+            } else {
+                Self::deposit_event(RawEvent::Expired(lifespan.kitty_id));
+            }
+        }
+        if batch && reaped > 0 {
+            Self::deposit_event(RawEvent::ExpiredBatch(reaped, reaped_ids));
+        }
+        if reaped > 0 {
+            Self::deposit_event(RawEvent::LifespanHeapPruned(remaining as u64));
+        }
+        Self::pay_reaper_bounty(reaped);
+
+        weight
+    }
+
+    /// Move `ReaperBounty` per reaped kitty from the fee pot to `ReaperRewardDestination`.
+    /// Nothing is paid if the pot cannot cover the whole amount.
+    fn pay_reaper_bounty(reaped: u32) {
+        let bounty = T::ReaperBounty::get().saturating_mul(T::Balance::from(reaped));
+        if bounty.is_zero() {
+            return;
+        }
+        let pot = T::FeeDestination::get();
+        match <balances::Module<T> as Currency<_>>::withdraw(
+            &pot, bounty, WithdrawReason::Transfer, ExistenceRequirement::AllowDeath) {
+            Ok(payment) => {
+                <balances::Module<T> as Currency<_>>::resolve_creating(&T::ReaperRewardDestination::get(), payment);
+            }
+            Err(_) => runtime_io::print("pay_reaper_bounty(): The fee pot cannot cover the bounty"),
+        }
+    }
+
+    /// Take an expired kitty off the market and mark it frozen. Its life stage is
+    /// already past `could_transfer`/`could_breed`, the flag makes the state explicit.
+    fn freeze_token(kitty_id: T::Hash) {
+        <Kitties<T>>::mutate(kitty_id, |kitty| kitty.price = None);
+        Self::index_listing(kitty_id, false);
+        if !Self::is_frozen(kitty_id) {
+            if let Some(owner) = Self::owner_of(kitty_id) {
+                <FrozenKittyCount<T>>::mutate(&owner, |count| *count = count.saturating_add(1));
+            }
+        }
+        <Frozen<T>>::insert(kitty_id, true);
+    }
+
+    /// Freeze an expired kitty and hand it to the `ExpiryDestination` archive, which
+    /// is not bound by `MaxKittiesOwned`.
+    fn retire_token(kitty_id: T::Hash, archive: T::AccountId) {
+        Self::freeze_token(kitty_id);
+        let owner = match Self::owner_of(kitty_id) {
+            Some(owner) => owner,
+            None => return runtime_io::print("retire_token(): No owner for this kitty"),
+        };
+        if owner != archive && Self::move_kitty(owner, archive, kitty_id, Vec::new()).is_err() {
+            runtime_io::print("retire_token(): Could not move the kitty to the archive");
+        }
+    }
+
+    fn burn_token(kitty_id: T::Hash) {
+        // delete kitty. Its lifespan is already gone from the heaps, so the rest is
+        // removed even if the counters disagree with the maps.
+        let count = Self::all_kitties_count();
+        if count == 0 {
+            runtime_io::print("burn_token(): There is no kitty.");
+        } else {
+            let last_kitty_index = count - 1;
+            let last_kitty_id = Self::kitty_by_index(last_kitty_index);
+            let kitty_index = <AllKittiesIndex<T>>::get(&kitty_id);
+            <AllKittiesArray<T>>::insert(kitty_index, &last_kitty_id);
+            <AllKittiesArray<T>>::remove(last_kitty_index);
+            <AllKittiesIndex<T>>::insert(last_kitty_id, kitty_index);
+            AllKittiesCount::put(last_kitty_index);
+        }
+        <AllKittiesIndex<T>>::remove(&kitty_id);
+
+        <Kitties<T>>::remove(kitty_id);
+
+        // delete owner ship, skipped with a log if the counters disagree with the maps
+        match Self::owner_of(&kitty_id) {
+            None => runtime_io::print("burn_token(): No owner for this kitty"),
+            Some(owner) => {
+                if Self::is_frozen(kitty_id) {
+                    <FrozenKittyCount<T>>::mutate(&owner, |count| *count = count.saturating_sub(1));
+                }
+                let owned_count = Self::owned_kitty_count(&owner);
+                if owned_count == 0 {
+                    runtime_io::print("burn_token(): There is no ownership information");
+                } else {
+                    let last_owned_index = owned_count - 1;
+                    let last_owned_id = Self::kitty_of_owner_by_index((owner.clone(), last_owned_index));
+                    let owned_index = <OwnedKittiesIndex<T>>::get(&kitty_id);
+                    <OwnedKittiesArray<T>>::insert((owner.clone(), owned_index), &last_owned_id);
+                    <OwnedKittiesArray<T>>::remove((owner.clone(), last_owned_index));
+                    <OwnedKittiesIndex<T>>::insert(last_owned_id, owned_index);
+                    <OwnedKittiesCount<T>>::insert(owner, last_owned_index);
+                }
+            }
+        }
+        <OwnedKittiesIndex<T>>::remove(&kitty_id);
+
+        <KittyOwner<T>>::remove(kitty_id);
+        Self::purge_auxiliary(kitty_id);
+    }
+
+    /// Drop everything keyed by a burned kitty outside the core kitty and ownership
+    /// maps. New per-kitty storage must be cleaned up here.
+    fn purge_auxiliary(kitty_id: T::Hash) {
+        Self::index_listing(kitty_id, false);
+        <Accessories<T>>::remove(kitty_id);
+        <Experience<T>>::remove(kitty_id);
+        Self::release_name(kitty_id);
+        <Fancy<T>>::remove(kitty_id);
+        <Frozen<T>>::remove(kitty_id);
+        <Soulbound<T>>::remove(kitty_id);
+        <OwnerHistory<T>>::remove(kitty_id);
+        for account in <FavoritedBy<T>>::take(kitty_id) {
+            <Favorites<T>>::remove((account.clone(), kitty_id));
+            <FavoriteCount<T>>::mutate(&account, |count| *count = count.saturating_sub(1));
+        }
+        Self::release_offers(kitty_id);
+        Self::release_swaps(kitty_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use runtime_io::with_externalities;
+    use primitives::{H256, Blake2Hasher};
+    use support::{impl_outer_origin, impl_outer_event, assert_ok, assert_noop, parameter_types};
+    use primitives::offchain::testing::TestOffchainExt;
+    use sr_primitives::{traits::{BlakeTwo256, IdentityLookup, OffchainWorker, OnFinalize, OnInitialize}, testing::Header};
+    use sr_primitives::weights::Weight;
+    use sr_primitives::Perbill;
+    use timestamp;
+    use mtp::MedianTime;
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    impl_outer_origin! {
+      pub enum Origin for Test {}
+    }
+
+    mod kitties {
+        pub use crate::kitties::Event;
+    }
+
+    impl_outer_event! {
+        pub enum TestEvent for Test {
+            balances<T>,
+            kitties<T>,
+        }
+    }
+
+    // For testing the module, we construct most of a mock runtime. This means
+    // first constructing a configuration type (`Test`) which `impl`s each of the
+    // configuration traits of modules we want to use.
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+    parameter_types! {
+      pub const BlockHashCount: u64 = 250;
+      pub const MaximumBlockWeight: Weight = 1_000_000;
+      pub const MaximumBlockLength: u32 = 2 * 1024;
+      pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+    }
+
+    impl system::Trait for Test {
+        type Origin = Origin;
+        type Call = ();
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type AccountId = u64;
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type WeightMultiplierUpdate = ();
+        type Header = Header;
+        type Event = TestEvent;
+        type BlockHashCount = BlockHashCount;
+        type MaximumBlockWeight = MaximumBlockWeight;
+        type MaximumBlockLength = MaximumBlockLength;
+        type AvailableBlockRatio = AvailableBlockRatio;
+        type Version = ();
+    }
+
+    impl balances::Trait for Test {
+        type Balance = u64;
+        type OnFreeBalanceZero = ();
+        type OnNewAccount = ();
+        type TransactionPayment = ();
+        type TransferPayment = ();
+        type DustRemoval = ();
+        type Event = TestEvent;
+        type ExistentialDeposit = ();
+        type TransferFee = ();
+        type CreationFee = ();
+        type TransactionBaseFee = ();
+        type TransactionByteFee = ();
+        type WeightToFee = ();
+    }
+
+    impl timestamp::Trait for Test {
+        type Moment = u64;
+        type OnTimestampSet = ();
+        type MinimumPeriod = ();
+    }
+
+    thread_local! {
+        static MEDIAN_TIME: Cell<u64> = Cell::new(0);
+    }
+
+    pub struct TestTime;
+
+    impl mtp::MedianTime for TestTime {
+        type Moment = u64;
+        fn median_time_past() -> u64 {
+            MEDIAN_TIME.with(|time| time.get())
+        }
+    }
+
+    fn set_mtp(moment: u64) {
+        MEDIAN_TIME.with(|time| time.set(moment));
+    }
+
+    parameter_types! {
+      pub const MarketplaceFee: Percent = Percent::from_percent(10);
+      pub const FeeDestination: u64 = 99;
+      pub const ReportExpiring: bool = true;
+      pub const ExpiryWarningWindow: u64 = 1_000;
+      pub const DailyBreedLimit: u32 = 2;
+      pub const MaxMemoLength: u32 = 16;
+      pub const MaxAccessories: u32 = 2;
+      pub const MinBalanceToCreate: u64 = 1_000;
+      pub const MaxNameLength: u32 = 16;
+    }
+
+    thread_local! {
+        static TRANSFER_COOLDOWN: Cell<u64> = Cell::new(0);
+    }
+
+    parameter_types! {
+      pub const OfferTtl: u64 = 100;
+      pub const MaxOffersPerKitty: u32 = 2;
+      pub const MaxKittiesOwned: u64 = 8;
+      pub const ReaperBounty: u64 = 10;
+      pub const ReaperRewardDestination: u64 = 98;
+      pub const ReferralPercent: Percent = Percent::from_percent(20);
+      pub const MaxOwnerHistory: u32 = 3;
+      pub const MaxFavorites: u32 = 2;
+      pub const MaxFavoritesPerKitty: u32 = 2;
+      pub const MaxBatchPurchases: u32 = 3;
+      pub const SwapDeposit: u64 = 50;
+    }
+
+    thread_local! {
+        static CREATOR_GATING: Cell<bool> = Cell::new(false);
+    }
+
+    thread_local! {
+        static MIN_LISTING_PRICE: Cell<u64> = Cell::new(0);
+    }
+
+    pub struct TestMinListingPrice;
+
+    impl Get<u64> for TestMinListingPrice {
+        fn get() -> u64 {
+            MIN_LISTING_PRICE.with(|floor| floor.get())
+        }
+    }
+
+    thread_local! {
+        static SALE_GRACE_PERIOD: Cell<u64> = Cell::new(0);
+    }
+
+    pub struct TestSaleGracePeriod;
+
+    impl Get<u64> for TestSaleGracePeriod {
+        fn get() -> u64 {
+            SALE_GRACE_PERIOD.with(|grace| grace.get())
+        }
+    }
+
+    thread_local! {
+        static GENERATION_LIFETIME_PERCENT: Cell<u32> = Cell::new(100);
+    }
+
+    pub struct TestGenerationLifetimePercent;
+
+    impl Get<u32> for TestGenerationLifetimePercent {
+        fn get() -> u32 {
+            GENERATION_LIFETIME_PERCENT.with(|percent| percent.get())
+        }
+    }
+
+    thread_local! {
+        static EXPIRY_DESTINATION: Cell<Option<u64>> = Cell::new(None);
+    }
+
+    pub struct TestExpiryDestination;
+
+    impl Get<Option<u64>> for TestExpiryDestination {
+        fn get() -> Option<u64> {
+            EXPIRY_DESTINATION.with(|destination| destination.get())
+        }
+    }
+
+    thread_local! {
+        static BREED_FEE: Cell<(u64, u64)> = Cell::new((0, 0));
+    }
+
+    pub struct TestBreedFeeBase;
+
+    impl Get<u64> for TestBreedFeeBase {
+        fn get() -> u64 {
+            BREED_FEE.with(|fee| fee.get().0)
+        }
+    }
+
+    pub struct TestBreedFeeStep;
+
+    impl Get<u64> for TestBreedFeeStep {
+        fn get() -> u64 {
+            BREED_FEE.with(|fee| fee.get().1)
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    enum TestWindow {
+        Maturity,
+        FirstHalfOfMaturity,
+        IntoEarlyOldness,
+    }
+
+    thread_local! {
+        static BREEDING_WINDOW: Cell<TestWindow> = Cell::new(TestWindow::Maturity);
+    }
+
+    pub struct TestBreedingWindow;
+
+    impl BreedingWindow<u64> for TestBreedingWindow {
+        fn breeding_end(lifetime: &Lifetime<u64>) -> u64 {
+            match BREEDING_WINDOW.with(|window| window.get()) {
+                TestWindow::Maturity => lifetime.old_time,
+                TestWindow::FirstHalfOfMaturity =>
+                    lifetime.maturity_time + (lifetime.old_time - lifetime.maturity_time) / 2,
+                TestWindow::IntoEarlyOldness => lifetime.old_time + (lifetime.end_time - lifetime.old_time) / 2,
+            }
+        }
+    }
+
+    thread_local! {
+        static FACTOR_CAPS: Cell<(u8, u8, u8)> = Cell::new((255, 255, 255));
+    }
+
+    pub struct TestMaxYoungFactor;
+
+    impl Get<u8> for TestMaxYoungFactor {
+        fn get() -> u8 {
+            FACTOR_CAPS.with(|caps| caps.get().0)
+        }
+    }
+
+    pub struct TestMaxMaturityFactor;
+
+    impl Get<u8> for TestMaxMaturityFactor {
+        fn get() -> u8 {
+            FACTOR_CAPS.with(|caps| caps.get().1)
+        }
+    }
+
+    pub struct TestMaxOldnessFactor;
+
+    impl Get<u8> for TestMaxOldnessFactor {
+        fn get() -> u8 {
+            FACTOR_CAPS.with(|caps| caps.get().2)
+        }
+    }
+
+    thread_local! {
+        static LISTING_TTL: Cell<u64> = Cell::new(0);
+    }
+
+    pub struct TestListingTtl;
+
+    impl Get<u64> for TestListingTtl {
+        fn get() -> u64 {
+            LISTING_TTL.with(|ttl| ttl.get())
+        }
+    }
+
+    thread_local! {
+        static FORCED_DNA_BYTE: Cell<Option<u8>> = Cell::new(None);
+    }
+
+    pub struct TestDnaGenerator;
+
+    impl DnaGenerator<H256> for TestDnaGenerator {
+        fn generate(seed: H256) -> H256 {
+            let mut dna = seed;
+            if let Some(byte) = FORCED_DNA_BYTE.with(|forced| forced.get()) {
+                dna.as_mut()[3] = byte;
+            }
+            dna
+        }
+    }
+
+    thread_local! {
+        static SCHEDULED_TRANSFERS: RefCell<Vec<H256>> = RefCell::new(Vec::new());
+    }
+
+    // Stands in for a scheduler holding kitties with a pending transfer.
+    pub struct TestScheduler;
+
+    impl TestScheduler {
+        fn schedule(kitty_id: H256) {
+            SCHEDULED_TRANSFERS.with(|scheduled| scheduled.borrow_mut().push(kitty_id));
+        }
+
+        fn cancel(kitty_id: H256) {
+            SCHEDULED_TRANSFERS.with(|scheduled| scheduled.borrow_mut().retain(|id| *id != kitty_id));
+        }
+    }
+
+    impl IsEncumbered<H256> for TestScheduler {
+        fn is_encumbered(kitty_id: &H256) -> bool {
+            SCHEDULED_TRANSFERS.with(|scheduled| scheduled.borrow().contains(kitty_id))
+        }
+    }
+
+    thread_local! {
+        static ASSET_BALANCES: RefCell<HashMap<(u32, u64), u64>> = RefCell::new(HashMap::new());
+        static ASSET_BLOCKED: RefCell<Vec<u64>> = RefCell::new(Vec::new());
+        static ASSET_BROKEN: RefCell<Vec<u64>> = RefCell::new(Vec::new());
+    }
+
+    pub struct TestAssets;
+
+    impl TestAssets {
+        fn set_balance(asset: u32, who: u64, amount: u64) {
+            ASSET_BALANCES.with(|balances| balances.borrow_mut().insert((asset, who), amount));
+        }
+
+        /// Make every asset transfer to `who` fail.
+        fn block(who: u64) {
+            ASSET_BLOCKED.with(|blocked| blocked.borrow_mut().push(who));
+        }
+
+        /// Make asset transfers to `who` fail even though `can_transfer` accepts them.
+        fn break_transfers_to(who: u64) {
+            ASSET_BROKEN.with(|broken| broken.borrow_mut().push(who));
+        }
+    }
+
+    /// Asset 0 stands in for one that does not exist.
+    impl MultiAssets<u64, u32, u64> for TestAssets {
+        fn exists(asset: u32) -> bool {
+            asset != 0
+        }
+
+        fn free_balance(asset: u32, who: &u64) -> u64 {
+            ASSET_BALANCES.with(|balances| balances.borrow().get(&(asset, *who)).cloned().unwrap_or(0))
+        }
+
+        fn can_transfer(asset: u32, from: &u64, to: &u64, amount: u64) -> Result {
+            ensure!(Self::free_balance(asset, from) >= amount, "Insufficient asset balance");
+            ensure!(!ASSET_BLOCKED.with(|blocked| blocked.borrow().contains(to)), "Asset recipient is blocked");
+            Ok(())
+        }
+
+        fn transfer(asset: u32, from: &u64, to: &u64, amount: u64) -> Result {
+            Self::can_transfer(asset, from, to, amount)?;
+            ensure!(!ASSET_BROKEN.with(|broken| broken.borrow().contains(to)), "Asset transfer failed");
+            let from_balance = Self::free_balance(asset, from);
+            Self::set_balance(asset, *from, from_balance - amount);
+            Self::set_balance(asset, *to, Self::free_balance(asset, to) + amount);
+            Ok(())
+        }
+    }
+
+    thread_local! {
+        static EMIT_DNA_SUMMARY: Cell<bool> = Cell::new(false);
+    }
+
+    pub struct TestEmitDnaSummary;
+
+    impl Get<bool> for TestEmitDnaSummary {
+        fn get() -> bool {
+            EMIT_DNA_SUMMARY.with(|emit| emit.get())
+        }
+    }
+
+    pub struct TestCreatorGating;
+
+    impl Get<bool> for TestCreatorGating {
+        fn get() -> bool {
+            CREATOR_GATING.with(|gating| gating.get())
+        }
+    }
+
+    pub struct TestTransferCooldown;
+
+    impl Get<u64> for TestTransferCooldown {
+        fn get() -> u64 {
+            TRANSFER_COOLDOWN.with(|cooldown| cooldown.get())
+        }
+    }
+
+    pub struct TestFancyPatterns;
+
+    impl Get<Vec<FancyPattern>> for TestFancyPatterns {
+        fn get() -> Vec<FancyPattern> {
+            vec![
+                FancyPattern { id: 1, prefix: vec![0xca, 0xfe] },
+                FancyPattern { id: 2, prefix: vec![0xca] },
+            ]
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum HookCall {
+        Created(u64, H256),
+        Transferred(u64, u64, H256),
+    }
+
+    thread_local! {
+        static HOOK_CALLS: RefCell<Vec<HookCall>> = RefCell::new(Vec::new());
+    }
+
+    pub struct RecordingHook;
+
+    impl OnKittyCreated<u64, H256> for RecordingHook {
+        fn on_created(owner: &u64, kitty_id: &H256) {
+            HOOK_CALLS.with(|c| c.borrow_mut().push(HookCall::Created(*owner, *kitty_id)));
+        }
+    }
+
+    impl OnKittyTransferred<u64, H256> for RecordingHook {
+        fn on_transferred(from: &u64, to: &u64, kitty_id: &H256) {
+            HOOK_CALLS.with(|c| c.borrow_mut().push(HookCall::Transferred(*from, *to, *kitty_id)));
+        }
+    }
+
+    thread_local! {
+        static EXPIRY_MODE: Cell<ExpiryMode> = Cell::new(ExpiryMode::Burn);
+    }
+
+    pub struct TestExpiryBehavior;
+
+    impl Get<ExpiryMode> for TestExpiryBehavior {
+        fn get() -> ExpiryMode {
+            EXPIRY_MODE.with(|mode| mode.get())
+        }
+    }
+
+    thread_local! {
+        static EXPIRY_EVENTS: Cell<ExpiryEvents> = Cell::new(ExpiryEvents::PerKitty);
+    }
+
+    pub struct TestExpiryEvents;
+
+    impl Get<ExpiryEvents> for TestExpiryEvents {
+        fn get() -> ExpiryEvents {
+            EXPIRY_EVENTS.with(|events| events.get())
+        }
+    }
+
+    impl Trait for Test {
+        type Event = TestEvent;
+        type Time = TestTime;
+        type MarketplaceFee = MarketplaceFee;
+        type FeeDestination = FeeDestination;
+        type OnCreated = RecordingHook;
+        type OnTransferred = RecordingHook;
+        type ReportExpiring = ReportExpiring;
+        type ExpiryWarningWindow = ExpiryWarningWindow;
+        type DailyBreedLimit = DailyBreedLimit;
+        type MaxMemoLength = MaxMemoLength;
+        type ExpiryBehavior = TestExpiryBehavior;
+        type ExpiryEvents = TestExpiryEvents;
+        type MaxAccessories = MaxAccessories;
+        type MinBalanceToCreate = MinBalanceToCreate;
+        type MaxNameLength = MaxNameLength;
+        type FancyPatterns = TestFancyPatterns;
+        type TransferCooldown = TestTransferCooldown;
+        type OfferTtl = OfferTtl;
+        type MaxOffersPerKitty = MaxOffersPerKitty;
+        type MaxKittiesOwned = MaxKittiesOwned;
+        type ReaperBounty = ReaperBounty;
+        type ReaperRewardDestination = ReaperRewardDestination;
+        type AdminOrigin = system::EnsureRoot<u64>;
+        type CreatorGating = TestCreatorGating;
+        type ReferralPercent = ReferralPercent;
+        type MinListingPrice = TestMinListingPrice;
+        type SaleGracePeriod = TestSaleGracePeriod;
+        type MaxOwnerHistory = MaxOwnerHistory;
+        type MaxFavorites = MaxFavorites;
+        type MaxFavoritesPerKitty = MaxFavoritesPerKitty;
+        type GenerationLifetimePercent = TestGenerationLifetimePercent;
+        type MaxBatchPurchases = MaxBatchPurchases;
+        type SwapDeposit = SwapDeposit;
+        type ExpiryDestination = TestExpiryDestination;
+        type BreedFeeBase = TestBreedFeeBase;
+        type BreedFeeStep = TestBreedFeeStep;
+        type BreedingWindow = TestBreedingWindow;
+        type MaxYoungFactor = TestMaxYoungFactor;
+        type MaxMaturityFactor = TestMaxMaturityFactor;
+        type MaxOldnessFactor = TestMaxOldnessFactor;
+        type ListingTtl = TestListingTtl;
+        type DnaGenerator = TestDnaGenerator;
+        type Encumbrances = TestScheduler;
+        type WeightInfo = ();
+        type EmitDnaSummary = TestEmitDnaSummary;
+        type AssetId = u32;
+        type Assets = TestAssets;
+    }
+
+    type TemplateModule = Module<Test>;
+    type Balances = balances::Module<Test>;
+    type System = system::Module<Test>;
+
+    // This function basically just builds a genesis storage key/value store according to
+    // our desired mockup.
+    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+        let mut t = system::GenesisConfig::default().build_storage::<Test>().unwrap();
+        balances::GenesisConfig::<Test> {
+            balances: vec![(1, 10_000), (2, 10_000), (3, 10_000)],
+            vesting: vec![],
+        }.assimilate_storage(&mut t).unwrap();
+        t.into()
+    }
+
+    fn create_kitty_for(owner: u64) -> H256 {
+        assert_ok!(TemplateModule::create_kitty(Origin::signed(owner)));
+        TemplateModule::kitty_of_owner_by_index((owner, TemplateModule::owned_kitty_count(owner) - 1))
+    }
+
+    fn kitty_events() -> Vec<RawEvent<u64, H256, u64, u32>> {
+        System::events().into_iter()
+            .filter_map(|record| match record.event {
+                TestEvent::kitties(event) => Some(event),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Creates two kitties for `owner` and moves the median time past to when both are mature.
+    fn create_mature_pair(owner: u64) -> (H256, H256) {
+        let first = create_kitty_for(owner);
+        let second = create_kitty_for(owner);
+        let mature_at = cmp::max(TemplateModule::kitty(first).lifetime.maturity_time,
+            TemplateModule::kitty(second).lifetime.maturity_time);
+        set_mtp(mature_at);
+        (first, second)
+    }
+
+    #[test]
+    fn generate_lifetime_test() {
+        with_externalities(&mut new_test_ext(), || {
+            let dna = H256::from_str(
+                "0000000000000000000000000000000000000000000000000000000000000000"
+            ).unwrap();
+            let birth_time: u64 = 100;
+            let maturity_time = birth_time + BASE_YOUNG_FACTOR as u64 * ONE_MINUTE;
+            let old_time = maturity_time + BASE_MATURITY_FACTOR as u64 * ONE_DAY;
+            let end_time = old_time + BASE_OLDNESS_FACTOR as u64 * ONE_MINUTE;
+            assert_ok!(TemplateModule::generate_lifetime(100, dna, 0),
+                Lifetime{ birth_time, maturity_time, old_time, end_time, });
+
+            let dna = H256::from_str(
+                "0203040000000000000000000000000000000000000000000000000000000000"
+            ).unwrap();
+            let birth_time: u64 = 100;
+            let maturity_time = birth_time + (BASE_YOUNG_FACTOR + 2) as u64 * ONE_MINUTE;
+            let old_time = maturity_time + (BASE_MATURITY_FACTOR + 3) as u64 * ONE_DAY;
+            let end_time = old_time + (BASE_OLDNESS_FACTOR + 4) as u64 * ONE_MINUTE;
+            assert_ok!(TemplateModule::generate_lifetime(100, dna, 0),
+                Lifetime{ birth_time, maturity_time, old_time, end_time, });
+        });
+    }
+
+    #[test]
+    fn life_stage_test() {
+        with_externalities(&mut new_test_ext(), || {
+            let lifetime = Lifetime {
+                birth_time: 100,
+                maturity_time: 200,
+                old_time: 300,
+                end_time: 400,
+            };
+            assert_eq!(TemplateModule::life_stage(90, &lifetime), LifeStage::Invalid);
+            assert_eq!(TemplateModule::life_stage(100, &lifetime), LifeStage::Young);
+            assert_eq!(TemplateModule::life_stage(199, &lifetime), LifeStage::Young);
+            assert_eq!(TemplateModule::life_stage(200, &lifetime), LifeStage::Maturity);
+            assert_eq!(TemplateModule::life_stage(299, &lifetime), LifeStage::Maturity);
+            assert_eq!(TemplateModule::life_stage(300, &lifetime), LifeStage::Oldness);
+            assert_eq!(TemplateModule::life_stage(350, &lifetime), LifeStage::Oldness);
+            assert_eq!(TemplateModule::life_stage(400, &lifetime), LifeStage::Invalid);
+            assert_eq!(TemplateModule::life_stage(500, &lifetime), LifeStage::Invalid);
+        });
+    }
+
+    #[test]
+    fn life_stage_limit_test() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty = Kitty {
+                id: H256::default(),
+                dna: H256::default(),
+                price: None,
+                gen: 0,
+                lifetime: Lifetime {
+                    birth_time: 100,
+                    maturity_time: 200,
+                    old_time: 300,
+                    end_time: 400,
+                },
+                breed_count: 0,
+                last_transfer: None,
+            };
+
+            assert_eq!(TemplateModule::could_breed(199, &kitty), false);
+            assert_eq!(TemplateModule::could_breed(200, &kitty), true);
+            assert_eq!(TemplateModule::could_breed(210, &kitty), true);
+            assert_eq!(TemplateModule::could_breed(300, &kitty), false);
+            assert_eq!(TemplateModule::could_breed(310, &kitty), false);
+
+            assert_eq!(TemplateModule::could_transfer(99, &kitty), false);
+            assert_eq!(TemplateModule::could_transfer(100, &kitty), true);
+            assert_eq!(TemplateModule::could_transfer(110, &kitty), true);
+            assert_eq!(TemplateModule::could_transfer(200, &kitty), true);
+            assert_eq!(TemplateModule::could_transfer(210, &kitty), true);
+            assert_eq!(TemplateModule::could_transfer(300, &kitty), false);
+            assert_eq!(TemplateModule::could_transfer(400, &kitty), false);
+        });
+    }
+
+    #[test]
+    fn buy_kitty_pays_marketplace_fee() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(1000)));
+            assert_ok!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 1000, vec![], None));
+
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(2));
+            assert_eq!(Balances::free_balance(&2), 9_000);
+            assert_eq!(Balances::free_balance(&99), 100);
+            assert_eq!(Balances::free_balance(&1), 10_900);
+        });
+    }
+
+    #[test]
+    fn marketplace_fee_never_exceeds_price() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(TemplateModule::marketplace_fee(1), 0);
+            assert_eq!(TemplateModule::marketplace_fee(3), 0);
+            assert_eq!(TemplateModule::marketplace_fee(1000), 100);
+
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(3)));
+            assert_ok!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 3, vec![], None));
+
+            assert_eq!(Balances::free_balance(&2), 9_997);
+            assert_eq!(Balances::free_balance(&99), 0);
+            assert_eq!(Balances::free_balance(&1), 10_003);
+        });
+    }
+
+    #[test]
+    fn buy_kitty_moves_no_funds_when_buyer_cannot_pay() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(20_000)));
+            assert!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 20_000, vec![], None).is_err());
+
+            assert_eq!(Balances::free_balance(&1), 10_000);
+            assert_eq!(Balances::free_balance(&99), 0);
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(1));
+        });
+    }
+
+    #[test]
+    fn hooks_fire_on_create_and_transfer() {
+        with_externalities(&mut new_test_ext(), || {
+            HOOK_CALLS.with(|c| c.borrow_mut().clear());
+
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, vec![]));
+
+            assert_eq!(HOOK_CALLS.with(|c| c.borrow().clone()), vec![
+                HookCall::Created(1, kitty_id),
+                HookCall::Transferred(1, 2, kitty_id),
+            ]);
+        });
+    }
+
+    #[test]
+    fn offchain_worker_reports_expiring_kitties() {
+        let mut t = new_test_ext();
+        let (offchain, _state) = TestOffchainExt::new();
+        t.set_offchain_externalities(offchain);
+        with_externalities(&mut t, || {
+            let first = create_kitty_for(1);
+            let second = create_kitty_for(2);
+            let first_end = TemplateModule::kitty(first).lifetime.end_time;
+            let second_end = TemplateModule::kitty(second).lifetime.end_time;
+            let (soon, late) = if first_end < second_end { (first, second) } else { (second, first) };
+            let soon_end = cmp::min(first_end, second_end);
+            let late_end = cmp::max(first_end, second_end);
+
+            assert_eq!(TemplateModule::expiring_kitties(soon_end - 1_000, 1_000), Vec::<H256>::new());
+            assert_eq!(TemplateModule::expiring_kitties(soon_end - 999, 1_000), vec![soon]);
+            let mut both = TemplateModule::expiring_kitties(late_end, 1_000);
+            both.sort();
+            let mut expected = vec![soon, late];
+            expected.sort();
+            assert_eq!(both, expected);
+
+            set_mtp(soon_end - 999);
+            let heap_before = <LifespanHeap<Test>>::to_vec();
+            <TemplateModule as OffchainWorker<u64>>::offchain_worker(1);
+            assert_eq!(<LifespanHeap<Test>>::to_vec(), heap_before);
+            assert_eq!(
+                runtime_io::local_storage_get(StorageKind::PERSISTENT, EXPIRING_KITTIES_KEY),
+                Some(vec![soon].encode())
+            );
+        });
+    }
+
+    #[test]
+    fn kitty_json_round_trip() {
+        let kitty = Kitty {
+            id: H256::repeat_byte(1),
+            dna: H256::repeat_byte(2),
+            price: Some(42u64),
+            gen: 3,
+            lifetime: Lifetime {
+                birth_time: 100u64,
+                maturity_time: 200,
+                old_time: 300,
+                end_time: 400,
+            },
+            breed_count: 5,
+            last_transfer: Some(150),
+        };
+        let json = serde_json::to_string(&kitty).unwrap();
+        let decoded: Kitty<H256, u64, u64> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, kitty);
+    }
+
+    #[test]
+    fn migrate_from_v0() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = H256::repeat_byte(7);
+            let lifetime = Lifetime { birth_time: 100, maturity_time: 200, old_time: 300, end_time: 400 };
+            let old = KittyV0 { id: kitty_id, dna: kitty_id, price: 50u64, gen: 2, lifetime: lifetime.clone() };
+            unhashed::put(&<Kitties<Test>>::hashed_key_for(&kitty_id), &old);
+            <AllKittiesArray<Test>>::insert(0, kitty_id);
+            <AllKittiesCount>::put(1);
+            assert_eq!(TemplateModule::storage_version(), 0);
+
+            TemplateModule::migrate();
+
+            assert_eq!(TemplateModule::storage_version(), CURRENT_STORAGE_VERSION);
+            assert_eq!(TemplateModule::kitty(kitty_id), Kitty {
+                id: kitty_id,
+                dna: kitty_id,
+                price: Some(50),
+                gen: 2,
+                lifetime,
+                breed_count: 0,
+                last_transfer: None,
+            });
+        });
+    }
+
+    #[test]
+    fn migrate_is_a_no_op_when_up_to_date() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            <Kitties<Test>>::mutate(kitty_id, |kitty| kitty.breed_count = 3);
+            <StorageVersion>::put(CURRENT_STORAGE_VERSION);
+
+            TemplateModule::migrate();
+
+            assert_eq!(TemplateModule::kitty(kitty_id).breed_count, 3);
+        });
+    }
+
+    #[test]
+    fn set_price_updates_only_the_price() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            let before = TemplateModule::kitty(kitty_id);
+
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(500)));
+            assert_eq!(TemplateModule::kitty(kitty_id), Kitty { price: Some(500), ..before.clone() });
+
+            assert!(TemplateModule::set_price(Origin::signed(2), kitty_id, Some(600)).is_err());
+            set_mtp(before.lifetime.old_time);
+            assert!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(600)).is_err());
+            assert_eq!(TemplateModule::kitty(kitty_id).price, Some(500));
+
+            // An old kitty can still be taken off the market.
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, None));
+            assert_eq!(TemplateModule::kitty(kitty_id).price, None);
+            assert_eq!(TemplateModule::for_sale_count(), 0);
+        });
+    }
+
+    #[test]
+    fn breeding_is_rate_limited_per_day() {
+        with_externalities(&mut new_test_ext(), || {
+            let (first, second) = create_mature_pair(1);
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, second, Vec::new()));
+            let start = TestTime::median_time_past();
+
+            assert_ok!(TemplateModule::breed_kitty(Origin::signed(1), first, second));
+            assert_ok!(TemplateModule::breed_kitty(Origin::signed(1), first, second));
+            assert!(TemplateModule::breed_kitty(Origin::signed(1), first, second).is_err());
+            assert_eq!(TemplateModule::breed_window(1), (start, 2));
+
+            set_mtp(start + ONE_DAY - 1);
+            assert!(TemplateModule::breed_kitty(Origin::signed(1), first, second).is_err());
+            assert_ok!(TemplateModule::breed_kitty(Origin::signed(2), first, second));
+
+            set_mtp(start + ONE_DAY);
+            assert_ok!(TemplateModule::breed_kitty(Origin::signed(1), first, second));
+            assert_eq!(TemplateModule::breed_window(1), (start + ONE_DAY, 1));
+        });
+    }
+
+    #[test]
+    fn memo_is_carried_by_transfer_and_purchase_events() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, b"Happy birthday!".to_vec()));
+            assert!(kitty_events().contains(
+                &RawEvent::Transferred(1, 2, kitty_id, b"Happy birthday!".to_vec())));
+
+            assert_ok!(TemplateModule::set_price(Origin::signed(2), kitty_id, Some(100)));
+            assert_ok!(TemplateModule::buy_kitty(Origin::signed(3), kitty_id, 100, b"Mine now".to_vec(), None));
+            assert!(kitty_events().contains(
+                &RawEvent::Bought(3, 2, kitty_id, 100, b"Mine now".to_vec())));
+        });
+    }
+
+    #[test]
+    fn oversized_memo_is_rejected() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            let memo = vec![b'x'; 17];
+            assert_noop!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, memo.clone()),
+                "The memo is too long");
+
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(100)));
+            assert_noop!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 100, memo, None),
+                "The memo is too long");
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, vec![b'x'; 16]));
+        });
+    }
+
+    #[test]
+    fn expired_kitties_are_burned_in_burn_mode() {
+        with_externalities(&mut new_test_ext(), || {
+            EXPIRY_MODE.with(|mode| mode.set(ExpiryMode::Burn));
+            let kitty_id = create_kitty_for(1);
+            let end_time = TemplateModule::kitty(kitty_id).lifetime.end_time;
+
+            TemplateModule::remove_expired_kitties(end_time + 1, Weight::max_value());
+
+            assert!(!<Kitties<Test>>::exists(kitty_id));
+            assert_eq!(TemplateModule::owner_of(kitty_id), None);
+            assert_eq!(TemplateModule::owned_kitty_count(1), 0);
+            assert_eq!(TemplateModule::all_kitties_count(), 0);
+        });
+    }
+
+    #[test]
+    fn expiry_events_per_kitty_or_batched() {
+        with_externalities(&mut new_test_ext(), || {
+            EXPIRY_MODE.with(|mode| mode.set(ExpiryMode::Burn));
+            let first = create_kitty_for(1);
+            let second = create_kitty_for(2);
+            let end_time = cmp::max(TemplateModule::kitty(first).lifetime.end_time,
+                TemplateModule::kitty(second).lifetime.end_time);
+
+            TemplateModule::remove_expired_kitties(end_time + 1, Weight::max_value());
+            let events = kitty_events();
+            assert!(events.contains(&RawEvent::Expired(first)));
+            assert!(events.contains(&RawEvent::Expired(second)));
+        });
+
+        with_externalities(&mut new_test_ext(), || {
+            EXPIRY_MODE.with(|mode| mode.set(ExpiryMode::Burn));
+            EXPIRY_EVENTS.with(|events| events.set(ExpiryEvents::Batch));
+            let first = create_kitty_for(1);
+            let second = create_kitty_for(2);
+            let third = create_kitty_for(3);
+            let end_time = [first, second, third].iter()
+                .map(|id| TemplateModule::kitty(*id).lifetime.end_time)
+                .max().unwrap();
+
+            TemplateModule::remove_expired_kitties(end_time + 1, Weight::max_value());
+            let batches: Vec<_> = kitty_events().into_iter()
+                .filter_map(|event| match event {
+                    RawEvent::ExpiredBatch(count, ids) => Some((count, ids)),
+                    RawEvent::Expired(_) => panic!("no per-kitty events in batch mode"),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(batches.len(), 1);
+            let (count, mut ids) = batches[0].clone();
+            ids.sort();
+            let mut expected = vec![first, second, third];
+            expected.sort();
+            assert_eq!(count, 3);
+            assert_eq!(ids, expected);
+
+            // Nothing left to reap, so no empty batch either.
+            TemplateModule::remove_expired_kitties(end_time + 2, Weight::max_value());
+            assert_eq!(kitty_events().iter().filter(|e| match e {
+                RawEvent::ExpiredBatch(..) => true,
+                _ => false,
+            }).count(), 1);
+        });
+    }
+
+    #[test]
+    fn expired_kitties_are_kept_in_freeze_mode() {
+        with_externalities(&mut new_test_ext(), || {
+            EXPIRY_MODE.with(|mode| mode.set(ExpiryMode::Freeze));
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(100)));
+            assert_ok!(TemplateModule::equip_accessory(Origin::signed(1), kitty_id, 7));
+            let end_time = TemplateModule::kitty(kitty_id).lifetime.end_time;
+
+            set_mtp(end_time + 1);
+            TemplateModule::remove_expired_kitties(end_time + 1, Weight::max_value());
+
+            assert!(TemplateModule::is_frozen(kitty_id));
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(1));
+            assert_eq!(TemplateModule::all_kitties_count(), 1);
+            assert_eq!(TemplateModule::kitty(kitty_id).price, None);
+            assert_noop!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, vec![]), "This cat is frozen");
+            assert_noop!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 100, vec![], None), "This cat is frozen");
+            assert!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(100)).is_err());
+            assert!(TemplateModule::breed_kitty(Origin::signed(1), kitty_id, kitty_id).is_err());
+            assert_noop!(TemplateModule::set_name(Origin::signed(1), kitty_id, b"Tom".to_vec()), "This cat is frozen");
+            assert_noop!(TemplateModule::equip_accessory(Origin::signed(1), kitty_id, 8), "This cat is frozen");
+            assert_noop!(TemplateModule::unequip_accessory(Origin::signed(1), kitty_id, 7), "This cat is frozen");
+        });
+    }
+
+    #[test]
+    fn migrate_from_v1() {
+        with_externalities(&mut new_test_ext(), || {
+            let lifetime = Lifetime { birth_time: 100, maturity_time: 200, old_time: 300, end_time: 400 };
+            for (index, price) in [0u64, 70].iter().enumerate() {
+                let kitty_id = H256::repeat_byte(index as u8 + 1);
+                let old = KittyV1 {
+                    id: kitty_id, dna: kitty_id, price: *price, gen: 1, lifetime: lifetime.clone(), breed_count: 4,
+                };
+                unhashed::put(&<Kitties<Test>>::hashed_key_for(&kitty_id), &old);
+                <AllKittiesArray<Test>>::insert(index as u64, kitty_id);
+            }
+            <AllKittiesCount>::put(2);
+            <StorageVersion>::put(1);
+
+            TemplateModule::migrate();
+
+            let not_for_sale = TemplateModule::kitty(H256::repeat_byte(1));
+            assert_eq!(not_for_sale.price, None);
+            assert_eq!(not_for_sale.breed_count, 4);
+            assert_eq!(TemplateModule::kitty(H256::repeat_byte(2)).price, Some(70));
+            assert_eq!(TemplateModule::storage_version(), CURRENT_STORAGE_VERSION);
+        });
+    }
+
+    #[test]
+    fn kitty_can_be_listed_and_bought_for_zero() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_noop!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 0, vec![], None),
+                "The cat you want to buy is not for sale");
+
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(0)));
+            assert_ok!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 0, vec![], None));
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(2));
+            assert_eq!(TemplateModule::kitty(kitty_id).price, None);
+            assert_eq!(Balances::free_balance(&1), 10_000);
+
+            assert_ok!(TemplateModule::set_price(Origin::signed(2), kitty_id, Some(10)));
+            assert_ok!(TemplateModule::set_price(Origin::signed(2), kitty_id, None));
+            assert_noop!(TemplateModule::buy_kitty(Origin::signed(1), kitty_id, 10, vec![], None),
+                "The cat you want to buy is not for sale");
+        });
+    }
+
+    #[test]
+    fn time_to_maturity_and_death() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(TemplateModule::time_to_maturity(H256::default()), None);
+            assert_eq!(TemplateModule::time_to_death(H256::default()), None);
+
+            let kitty_id = create_kitty_for(1);
+            let lifetime = TemplateModule::kitty(kitty_id).lifetime;
+
+            assert_eq!(TemplateModule::time_to_maturity(kitty_id), Some(lifetime.maturity_time));
+            assert_eq!(TemplateModule::time_to_death(kitty_id), Some(lifetime.end_time));
+
+            set_mtp(lifetime.maturity_time - 10);
+            assert_eq!(TemplateModule::time_to_maturity(kitty_id), Some(10));
+            assert_eq!(TemplateModule::time_to_death(kitty_id), Some(lifetime.end_time - lifetime.maturity_time + 10));
+
+            set_mtp(lifetime.end_time - 1);
+            assert_eq!(TemplateModule::time_to_maturity(kitty_id), Some(0));
+            assert_eq!(TemplateModule::time_to_death(kitty_id), Some(1));
+
+            set_mtp(lifetime.end_time + 1_000);
+            assert_eq!(TemplateModule::time_to_death(kitty_id), Some(0));
+        });
+    }
+
+    #[test]
+    fn entering_oldness_is_announced_once() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            let lifetime = TemplateModule::kitty(kitty_id).lifetime;
+            let oldness_events = || kitty_events().into_iter()
+                .filter(|event| *event == RawEvent::EnteringOldness(kitty_id))
+                .count();
+
+            set_mtp(lifetime.old_time - 1);
+            <TemplateModule as OnFinalize<u64>>::on_finalize(1);
+            assert_eq!(oldness_events(), 0);
+
+            // The boundary block itself, when `life_stage` turns `Oldness`.
+            set_mtp(lifetime.old_time);
+            assert_eq!(TemplateModule::life_stage(lifetime.old_time, &lifetime), LifeStage::Oldness);
+            <TemplateModule as OnFinalize<u64>>::on_finalize(2);
+            assert_eq!(oldness_events(), 1);
+
+            set_mtp(lifetime.old_time + 2);
+            <TemplateModule as OnFinalize<u64>>::on_finalize(3);
+            assert_eq!(oldness_events(), 1);
+            assert!(<Kitties<Test>>::exists(kitty_id));
+        });
+    }
+
+    #[test]
+    fn migrate_to_v3_queues_live_kitties() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            let kitty = TemplateModule::kitty(kitty_id);
+            let old_time = kitty.lifetime.old_time;
+            unhashed::put(&<Kitties<Test>>::hashed_key_for(&kitty_id), &KittyV2 {
+                id: kitty.id, dna: kitty.dna, price: kitty.price, gen: kitty.gen,
+                lifetime: kitty.lifetime, breed_count: kitty.breed_count,
+            });
+            <OldnessHeap<Test>>::from_vec(Vec::new());
+            <StorageVersion>::put(2);
+
+            TemplateModule::migrate();
+
+            assert_eq!(<OldnessHeap<Test>>::to_vec(), vec![Lifespan { kitty_id, end_time: old_time }]);
+        });
+    }
+
+    #[test]
+    fn accessories_are_capped_and_owner_only() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_noop!(TemplateModule::equip_accessory(Origin::signed(2), kitty_id, 7), "You do not own this cat");
+
+            assert_ok!(TemplateModule::equip_accessory(Origin::signed(1), kitty_id, 7));
+            assert_noop!(TemplateModule::equip_accessory(Origin::signed(1), kitty_id, 7),
+                "This accessory is already equipped");
+            assert_ok!(TemplateModule::equip_accessory(Origin::signed(1), kitty_id, 8));
+            assert_noop!(TemplateModule::equip_accessory(Origin::signed(1), kitty_id, 9),
+                "This cat cannot wear any more accessories");
+            assert_eq!(TemplateModule::accessories_of(kitty_id), vec![7, 8]);
+
+            assert_noop!(TemplateModule::unequip_accessory(Origin::signed(1), kitty_id, 9),
+                "This accessory is not equipped");
+            assert_ok!(TemplateModule::unequip_accessory(Origin::signed(1), kitty_id, 7));
+            assert_eq!(TemplateModule::accessories_of(kitty_id), vec![8]);
+            assert_ok!(TemplateModule::equip_accessory(Origin::signed(1), kitty_id, 9));
+
+            let events = kitty_events();
+            assert!(events.contains(&RawEvent::AccessoryEquipped(kitty_id, 9)));
+            assert!(events.contains(&RawEvent::AccessoryUnequipped(kitty_id, 7)));
+        });
+    }
+
+    #[test]
+    fn accessories_are_removed_on_burn() {
+        with_externalities(&mut new_test_ext(), || {
+            EXPIRY_MODE.with(|mode| mode.set(ExpiryMode::Burn));
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::equip_accessory(Origin::signed(1), kitty_id, 7));
+            let end_time = TemplateModule::kitty(kitty_id).lifetime.end_time;
+
+            TemplateModule::remove_expired_kitties(end_time + 1, Weight::max_value());
+
+            assert!(!<Accessories<Test>>::exists(kitty_id));
+        });
+    }
+
+    #[test]
+    fn transfers_and_breeding_earn_experience() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_eq!(TemplateModule::experience_of(kitty_id), 0);
+            assert_eq!(TemplateModule::level(kitty_id), 0);
+
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, Vec::new()));
+            assert_eq!(TemplateModule::experience_of(kitty_id), 1);
+            assert_eq!(TemplateModule::level(kitty_id), 1);
+
+            let (first, second) = create_mature_pair(3);
+            assert_ok!(TemplateModule::breed_kitty(Origin::signed(3), first, second));
+            assert_eq!(TemplateModule::experience_of(first), 1);
+            assert_eq!(TemplateModule::experience_of(second), 1);
+        });
+    }
+
+    #[test]
+    fn crossing_a_threshold_levels_up_once() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            let level_ups = || kitty_events().into_iter()
+                .filter(|event| *event == RawEvent::LeveledUp(kitty_id, 2))
+                .count();
+
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, Vec::new()));
+            assert_ok!(TemplateModule::transfer(Origin::signed(2), 1, kitty_id, Vec::new()));
+            assert_eq!(level_ups(), 0);
+
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, Vec::new()));
+            assert_eq!(TemplateModule::level(kitty_id), 2);
+            assert_eq!(level_ups(), 1);
+
+            assert_ok!(TemplateModule::transfer(Origin::signed(2), 1, kitty_id, Vec::new()));
+            assert_eq!(TemplateModule::level(kitty_id), 2);
+            assert_eq!(level_ups(), 1);
+        });
+    }
+
+    #[test]
+    fn create_kitty_requires_minimum_balance() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_noop!(TemplateModule::create_kitty(Origin::signed(4)),
+                "Not enough free balance to create a kitty");
+
+            Balances::make_free_balance_be(&4, 999);
+            assert_noop!(TemplateModule::create_kitty(Origin::signed(4)),
+                "Not enough free balance to create a kitty");
+
+            Balances::make_free_balance_be(&4, 1_000);
+            create_kitty_for(4);
+            assert_eq!(Balances::free_balance(&4), 1_000);
+        });
+    }
+
+    #[test]
+    fn names_are_unique_ignoring_case_and_whitespace() {
+        with_externalities(&mut new_test_ext(), || {
+            let first = create_kitty_for(1);
+            let second = create_kitty_for(2);
+
+            assert_ok!(TemplateModule::set_name(Origin::signed(1), first, b"Tom".to_vec()));
+            assert_eq!(TemplateModule::name_of(first), b"Tom".to_vec());
+            assert_eq!(TemplateModule::kitty_by_name(b"tom".to_vec()), Some(first));
+            assert!(kitty_events().contains(&RawEvent::NameSet(first, b"Tom".to_vec())));
+
+            assert_noop!(TemplateModule::set_name(Origin::signed(2), second, b" TOM ".to_vec()),
+                "This name is already taken");
+            assert_noop!(TemplateModule::set_name(Origin::signed(2), first, b"Jerry".to_vec()),
+                "You do not own this cat");
+            assert_noop!(TemplateModule::set_name(Origin::signed(2), second, vec![b'a'; 17]),
+                "The name is too long");
+
+            // Re-setting a kitty's own name in a different case is fine.
+            assert_ok!(TemplateModule::set_name(Origin::signed(1), first, b"TOM".to_vec()));
+            assert_eq!(TemplateModule::kitty_by_name(b"tom".to_vec()), Some(first));
+        });
+    }
+
+    #[test]
+    fn renaming_frees_the_old_name() {
+        with_externalities(&mut new_test_ext(), || {
+            let first = create_kitty_for(1);
+            let second = create_kitty_for(2);
+
+            assert_ok!(TemplateModule::set_name(Origin::signed(1), first, b"Tom".to_vec()));
+            assert_ok!(TemplateModule::set_name(Origin::signed(1), first, b"Felix".to_vec()));
+            assert_eq!(TemplateModule::kitty_by_name(b"tom".to_vec()), None);
+
+            assert_ok!(TemplateModule::set_name(Origin::signed(2), second, b"tom".to_vec()));
+            assert_eq!(TemplateModule::kitty_by_name(b"tom".to_vec()), Some(second));
+
+            assert_ok!(TemplateModule::set_name(Origin::signed(1), first, Vec::new()));
+            assert_eq!(TemplateModule::name_of(first), Vec::<u8>::new());
+            assert_eq!(TemplateModule::kitty_by_name(b"felix".to_vec()), None);
+        });
+    }
+
+    #[test]
+    fn burning_frees_the_name() {
+        with_externalities(&mut new_test_ext(), || {
+            EXPIRY_MODE.with(|mode| mode.set(ExpiryMode::Burn));
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_name(Origin::signed(1), kitty_id, b"Tom".to_vec()));
+            let end_time = TemplateModule::kitty(kitty_id).lifetime.end_time;
+
+            TemplateModule::remove_expired_kitties(end_time + 1, Weight::max_value());
+
+            assert_eq!(TemplateModule::kitty_by_name(b"tom".to_vec()), None);
+            assert!(!<Names<Test>>::exists(kitty_id));
+        });
+    }
+
+    #[test]
+    fn fancy_kind_matches_the_first_pattern() {
+        with_externalities(&mut new_test_ext(), || {
+            let dna = |hex: &str| H256::from_str(hex).unwrap();
+            assert_eq!(TemplateModule::fancy_kind(
+                &dna("cafe000000000000000000000000000000000000000000000000000000000000")), Some(1));
+            assert_eq!(TemplateModule::fancy_kind(
+                &dna("ca00000000000000000000000000000000000000000000000000000000000000")), Some(2));
+            assert_eq!(TemplateModule::fancy_kind(
+                &dna("00cafe0000000000000000000000000000000000000000000000000000000000")), None);
+        });
+    }
+
+    #[test]
+    fn minting_a_fancy_kitty_records_it() {
+        with_externalities(&mut new_test_ext(), || {
+            let fancy_id = H256::from_low_u64_be(1);
+            let fancy = Kitty {
+                id: fancy_id,
+                dna: H256::from_str("cafe000000000000000000000000000000000000000000000000000000000000").unwrap(),
+                ..Default::default()
+            };
+            assert_ok!(TemplateModule::mint(1, fancy_id, fancy));
+
+            let plain_id = H256::from_low_u64_be(2);
+            let plain = Kitty {
+                id: plain_id,
+                dna: H256::from_str("0100000000000000000000000000000000000000000000000000000000000000").unwrap(),
+                ..Default::default()
+            };
+            assert_ok!(TemplateModule::mint(1, plain_id, plain));
+
+            assert_eq!(TemplateModule::fancy_of(fancy_id), Some(1));
+            assert_eq!(TemplateModule::fancy_of(plain_id), None);
+            let events = kitty_events();
+            assert!(events.contains(&RawEvent::FancyMinted(fancy_id, 1)));
+            assert!(!events.iter().any(|event| match event {
+                RawEvent::FancyMinted(kitty_id, _) => *kitty_id == plain_id,
+                _ => false,
+            }));
+        });
+    }
+
+    #[test]
+    fn transfers_respect_the_cooldown() {
+        with_externalities(&mut new_test_ext(), || {
+            TRANSFER_COOLDOWN.with(|cooldown| cooldown.set(100));
+            let kitty_id = create_kitty_for(1);
+            set_mtp(10);
+
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, vec![]));
+            assert_eq!(TemplateModule::kitty(kitty_id).last_transfer, Some(10));
+            assert_noop!(TemplateModule::transfer(Origin::signed(2), 1, kitty_id, vec![]),
+                "This cat changed hands too recently");
+
+            assert_ok!(TemplateModule::set_price(Origin::signed(2), kitty_id, Some(10)));
+            assert_noop!(TemplateModule::buy_kitty(Origin::signed(3), kitty_id, 10, vec![], None),
+                "This cat changed hands too recently");
+
+            set_mtp(110);
+            assert_ok!(TemplateModule::buy_kitty(Origin::signed(3), kitty_id, 10, vec![], None));
+            assert_eq!(TemplateModule::kitty(kitty_id).last_transfer, Some(110));
+            assert_noop!(TemplateModule::transfer(Origin::signed(3), 1, kitty_id, vec![]),
+                "This cat changed hands too recently");
+        });
+    }
+
+    #[test]
+    fn migrate_to_v4_adds_last_transfer() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = H256::repeat_byte(3);
+            let lifetime = Lifetime { birth_time: 100, maturity_time: 200, old_time: 300, end_time: 400 };
+            let old = KittyV2 {
+                id: kitty_id, dna: kitty_id, price: Some(20u64), gen: 1, lifetime: lifetime.clone(), breed_count: 2,
+            };
+            unhashed::put(&<Kitties<Test>>::hashed_key_for(&kitty_id), &old);
+            <AllKittiesArray<Test>>::insert(0, kitty_id);
+            <AllKittiesCount>::put(1);
+            <StorageVersion>::put(3);
+
+            TemplateModule::migrate();
+
+            assert_eq!(TemplateModule::kitty(kitty_id), Kitty {
+                id: kitty_id,
+                dna: kitty_id,
+                price: Some(20),
+                gen: 1,
+                lifetime,
+                breed_count: 2,
+                last_transfer: None,
+            });
+            assert_eq!(TemplateModule::storage_version(), CURRENT_STORAGE_VERSION);
+        });
+    }
+
+    #[test]
+    fn accepting_an_offer_sells_the_kitty() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_noop!(TemplateModule::make_offer(Origin::signed(1), kitty_id, 100),
+                "You can't make an offer on your own cat");
+
+            assert_ok!(TemplateModule::make_offer(Origin::signed(2), kitty_id, 1_000));
+            assert_eq!(Balances::reserved_balance(&2), 1_000);
+            assert_noop!(TemplateModule::make_offer(Origin::signed(2), kitty_id, 500),
+                "You already have an offer on this cat");
+            assert_noop!(TemplateModule::accept_offer(Origin::signed(2), kitty_id, 2), "You do not own this cat");
+
+            assert_ok!(TemplateModule::accept_offer(Origin::signed(1), kitty_id, 2));
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(2));
+            assert_eq!(Balances::reserved_balance(&2), 0);
+            assert_eq!(Balances::free_balance(&2), 9_000);
+            assert_eq!(Balances::free_balance(&1), 10_900);
+            assert_eq!(Balances::free_balance(&99), 100);
+            assert!(TemplateModule::offers_for(kitty_id).is_empty());
+        });
+    }
+
+    #[test]
+    fn cancel_all_offers_unreserves_every_bidder() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::make_offer(Origin::signed(2), kitty_id, 300));
+            assert_ok!(TemplateModule::make_offer(Origin::signed(3), kitty_id, 400));
+
+            assert_noop!(TemplateModule::cancel_all_offers(Origin::signed(2), kitty_id), "You do not own this cat");
+            assert_ok!(TemplateModule::cancel_all_offers(Origin::signed(1), kitty_id));
+
+            assert!(TemplateModule::offers_for(kitty_id).is_empty());
+            assert_eq!(Balances::reserved_balance(&2), 0);
+            assert_eq!(Balances::reserved_balance(&3), 0);
+            assert_eq!(Balances::free_balance(&2), 10_000);
+            assert_eq!(Balances::free_balance(&3), 10_000);
+            assert!(kitty_events().contains(&RawEvent::AllOffersCancelled(kitty_id, 2)));
+            assert_noop!(TemplateModule::accept_offer(Origin::signed(1), kitty_id, 2),
+                "There is no offer from this buyer");
+        });
+    }
+
+    #[test]
+    fn offers_lapse_after_their_ttl() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            set_mtp(10);
+            assert_ok!(TemplateModule::make_offer(Origin::signed(2), kitty_id, 300));
+
+            set_mtp(109);
+            <TemplateModule as OnFinalize<u64>>::on_finalize(1);
+            assert_eq!(Balances::reserved_balance(&2), 300);
+
+            set_mtp(110);
+            assert_noop!(TemplateModule::accept_offer(Origin::signed(1), kitty_id, 2), "This offer has expired");
+            <TemplateModule as OnFinalize<u64>>::on_finalize(2);
+
+            assert_eq!(Balances::reserved_balance(&2), 0);
+            assert!(TemplateModule::offers_for(kitty_id).is_empty());
+            assert!(kitty_events().contains(&RawEvent::OfferExpired(2, kitty_id)));
+        });
+    }
+
+    #[test]
+    fn cancelled_offers_do_not_lapse_twice() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::make_offer(Origin::signed(2), kitty_id, 300));
+            assert_ok!(TemplateModule::cancel_offer(Origin::signed(2), kitty_id));
+            assert_eq!(Balances::reserved_balance(&2), 0);
+            set_mtp(50);
+            assert_ok!(TemplateModule::make_offer(Origin::signed(2), kitty_id, 200));
+
+            // The heap entry of the cancelled offer reaches the top but must not touch the new one.
+            set_mtp(100);
+            <TemplateModule as OnFinalize<u64>>::on_finalize(1);
+
+            assert_eq!(Balances::reserved_balance(&2), 200);
+            assert!(!kitty_events().contains(&RawEvent::OfferExpired(2, kitty_id)));
+        });
+    }
+
+    #[test]
+    fn phenotype_decodes_fixed_dna_bytes() {
+        with_externalities(&mut new_test_ext(), || {
+            let dna = H256::from_str(
+                "010203ff80400b0600000000000000000000000000000000000000000000ffff"
+            ).unwrap();
+            assert_eq!(TemplateModule::phenotype(&dna), Phenotype {
+                body_color: [0xff, 0x80, 0x40],
+                pattern: 0x0b % PATTERN_COUNT,
+                eye_shape: 0x06 % EYE_SHAPE_COUNT,
+            });
+            assert_eq!(TemplateModule::phenotype(&H256::default()), Phenotype::default());
+        });
+    }
+
+    #[test]
+    fn safe_transfer_checks_the_recipient_first() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            for _ in 0..MaxKittiesOwned::get() {
+                create_kitty_for(2);
+            }
+            assert_noop!(TemplateModule::create_kitty(Origin::signed(2)), "The recipient owns too many kitties");
+
+            assert_noop!(TemplateModule::safe_transfer(Origin::signed(1), 2, kitty_id),
+                "The recipient owns too many kitties");
+            assert_noop!(TemplateModule::safe_transfer(Origin::signed(1), 4, kitty_id),
+                "The recipient account does not exist");
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(10)));
+            assert_noop!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 10, vec![], None),
+                "The recipient owns too many kitties");
+
+            assert_ok!(TemplateModule::safe_transfer(Origin::signed(1), 3, kitty_id));
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(3));
+            assert_eq!(TemplateModule::owned_kitty_count(3), 1);
+        });
+    }
+
+    #[test]
+    fn simulate_lifetime_matches_generate_lifetime() {
+        with_externalities(&mut new_test_ext(), || {
+            let dna = H256::from_str(
+                "0a141e0000000000000000000000000000000000000000000000000000000000"
+            ).unwrap();
+            set_mtp(5);
+            assert_eq!(TemplateModule::simulate_lifetime(dna, 1_000), TemplateModule::generate_lifetime(1_000, dna, 0));
+            let lifetime = TemplateModule::simulate_lifetime(dna, 1_000).unwrap();
+            assert_eq!(lifetime.maturity_time, 1_000 + 15 * ONE_MINUTE);
+            assert_eq!(lifetime.old_time, lifetime.maturity_time + 30 * ONE_DAY);
+            assert_eq!(lifetime.end_time, lifetime.old_time + 35 * ONE_MINUTE);
+            assert_eq!(TemplateModule::simulate_lifetime(dna, 1_000).unwrap().birth_time, 1_000);
+            assert!(TemplateModule::simulate_lifetime(dna, u64::max_value()).is_err());
+        });
+    }
+
+    #[test]
+    fn capabilities_follow_the_life_stage() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            let lifetime = TemplateModule::kitty(kitty_id).lifetime;
+
+            assert_eq!(TemplateModule::capabilities(kitty_id), (false, true, true));
+
+            set_mtp(lifetime.maturity_time);
+            assert_eq!(TemplateModule::capabilities(kitty_id), (true, true, true));
+
+            set_mtp(lifetime.old_time);
+            assert_eq!(TemplateModule::capabilities(kitty_id), (false, false, false));
+
+            assert_eq!(TemplateModule::capabilities(H256::repeat_byte(9)), (false, false, false));
+        });
+    }
+
+    #[test]
+    fn capabilities_account_for_the_transfer_cooldown() {
+        with_externalities(&mut new_test_ext(), || {
+            TRANSFER_COOLDOWN.with(|cooldown| cooldown.set(100));
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, vec![]));
+
+            assert_eq!(TemplateModule::capabilities(kitty_id), (false, false, true));
+        });
+    }
+
+    #[test]
+    fn reaping_pays_a_bounty_per_kitty() {
+        with_externalities(&mut new_test_ext(), || {
+            Balances::make_free_balance_be(&99, 1_000);
+            let first = create_kitty_for(1);
+            let second = create_kitty_for(2);
+            let end_time = cmp::max(TemplateModule::kitty(first).lifetime.end_time,
+                TemplateModule::kitty(second).lifetime.end_time);
+
+            TemplateModule::remove_expired_kitties(end_time + 1, Weight::max_value());
+            assert_eq!(Balances::free_balance(&98), 2 * ReaperBounty::get());
+
+            set_mtp(end_time + 1);
+            let third = create_kitty_for(3);
+            let third_end = TemplateModule::kitty(third).lifetime.end_time;
+            TemplateModule::remove_expired_kitties(third_end + 1, Weight::max_value());
+
+            assert_eq!(Balances::free_balance(&98), 3 * ReaperBounty::get());
+            assert_eq!(Balances::free_balance(&99), 1_000 - 3 * ReaperBounty::get());
+        });
+    }
+
+    #[test]
+    fn no_bounty_is_paid_from_an_empty_pot() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            let end_time = TemplateModule::kitty(kitty_id).lifetime.end_time;
+
+            TemplateModule::remove_expired_kitties(end_time + 1, Weight::max_value());
+
+            assert_eq!(Balances::free_balance(&98), 0);
+            assert!(!<Kitties<Test>>::exists(kitty_id));
+        });
+    }
+
+    #[test]
+    fn for_sale_in_range_filters_listings_by_price() {
+        with_externalities(&mut new_test_ext(), || {
+            let mut kitties = Vec::new();
+            for price in [50u64, 100, 150, 200].iter() {
+                let kitty_id = create_kitty_for(1);
+                assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(*price)));
+                kitties.push(kitty_id);
+            }
+            create_kitty_for(1);
+
+            let mut in_range = TemplateModule::for_sale_in_range(100, 150);
+            in_range.sort();
+            let mut expected = vec![kitties[1], kitties[2]];
+            expected.sort();
+            assert_eq!(in_range, expected);
+
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitties[1], None));
+            assert_ok!(TemplateModule::buy_kitty(Origin::signed(2), kitties[2], 150, vec![], None));
+            assert!(TemplateModule::for_sale_in_range(100, 150).is_empty());
+            assert_eq!(TemplateModule::for_sale_in_range(0, 1_000).len(), 2);
+        });
+    }
+
+    #[test]
+    fn migrate_to_v5_indexes_listed_kitties() {
+        with_externalities(&mut new_test_ext(), || {
+            let listed = create_kitty_for(1);
+            create_kitty_for(1);
+            <Kitties<Test>>::mutate(listed, |kitty| kitty.price = Some(10));
+            <StorageVersion>::put(4);
+
+            TemplateModule::migrate();
+
+            assert_eq!(TemplateModule::for_sale_count(), 1);
+            assert_eq!(TemplateModule::for_sale_by_index(0), listed);
+        });
+    }
+
+    #[test]
+    fn lifespans_sort_by_end_time_then_kitty_id() {
+        let lifespan = |byte: u8, end_time: u64| Lifespan { kitty_id: H256::repeat_byte(byte), end_time };
+        let mut lifespans = vec![lifespan(1, 300), lifespan(3, 100), lifespan(2, 300), lifespan(4, 200)];
+
+        lifespans.sort();
+
+        assert_eq!(lifespans, vec![lifespan(3, 100), lifespan(4, 200), lifespan(1, 300), lifespan(2, 300)]);
+        assert!(lifespan(9, 100) < lifespan(1, 101));
+    }
+
+    #[test]
+    fn only_whitelisted_accounts_create_while_gated() {
+        with_externalities(&mut new_test_ext(), || {
+            CREATOR_GATING.with(|gating| gating.set(true));
+            assert_noop!(TemplateModule::create_kitty(Origin::signed(1)), "This account may not create kitties");
+
+            assert!(TemplateModule::add_creator(Origin::signed(1), 1).is_err());
+            assert_ok!(TemplateModule::add_creator(Origin::ROOT, 1));
+            assert!(kitty_events().contains(&RawEvent::CreatorAdded(1)));
+            create_kitty_for(1);
+
+            assert_ok!(TemplateModule::remove_creator(Origin::ROOT, 1));
+            assert_noop!(TemplateModule::create_kitty(Origin::signed(1)), "This account may not create kitties");
+        });
+    }
+
+    #[test]
+    fn breeding_and_ungated_creation_ignore_the_whitelist() {
+        with_externalities(&mut new_test_ext(), || {
+            create_kitty_for(2);
+            let (first, second) = create_mature_pair(1);
+
+            CREATOR_GATING.with(|gating| gating.set(true));
+            assert_ok!(TemplateModule::breed_kitty(Origin::signed(1), first, second));
+
+            CREATOR_GATING.with(|gating| gating.set(false));
+            create_kitty_for(3);
+        });
+    }
+
+    #[test]
+    fn referrer_takes_a_cut_of_the_sellers_proceeds() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(1_000)));
+
+            assert_ok!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 1_000, vec![], Some(3)));
+
+            assert_eq!(Balances::free_balance(&2), 9_000);
+            assert_eq!(Balances::free_balance(&99), 100);
+            assert_eq!(Balances::free_balance(&3), 10_200);
+            assert_eq!(Balances::free_balance(&1), 10_700);
+        });
+    }
+
+    #[test]
+    fn buying_without_a_referrer_pays_the_seller_in_full() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(1_000)));
+
+            assert_ok!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 1_000, vec![], None));
+
+            assert_eq!(Balances::free_balance(&3), 10_000);
+            assert_eq!(Balances::free_balance(&1), 10_900);
+        });
+    }
+
+    #[test]
+    fn self_referral_is_rejected() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(1_000)));
+
+            assert_noop!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 1_000, vec![], Some(2)),
+                "You can't refer yourself");
+        });
+    }
+
+    #[test]
+    fn lifespan_heap_accessors_match_storage() {
+        with_externalities(&mut new_test_ext(), || {
+            create_kitty_for(1);
+            create_kitty_for(2);
+
+            assert_eq!(<LifespanHeap<Test>>::to_vec(), <LifespanArray<Test>>::get());
+            assert_eq!(<LifespanHeap<Test>>::len(), 2);
+            assert_eq!(<LifespanHeap<Test>>::peek(), <LifespanArray<Test>>::get().first().cloned());
+        });
+    }
+
+    #[test]
+    fn life_stage_follows_the_injected_median_time() {
+        with_externalities(&mut new_test_ext(), || {
+            let (first, second) = create_mature_pair(1);
+            let lifetime = TemplateModule::kitty(first).lifetime;
+
+            set_mtp(lifetime.birth_time);
+            assert!(TemplateModule::breed_kitty(Origin::signed(1), first, second).is_err());
+            assert_eq!(TemplateModule::time_to_maturity(first), Some(lifetime.maturity_time - lifetime.birth_time));
+
+            set_mtp(lifetime.maturity_time);
+            assert_eq!(TemplateModule::time_to_maturity(first), Some(0));
+        });
+    }
+
+    #[test]
+    fn rebuild_lifespan_heap_reconciles_with_live_kitties() {
+        with_externalities(&mut new_test_ext(), || {
+            let first = create_kitty_for(1);
+            let second = create_kitty_for(2);
+            let lifespan_of = |kitty_id| Lifespan { kitty_id, end_time: TemplateModule::kitty(kitty_id).lifetime.end_time };
+
+            <LifespanHeap<Test>>::retain(|lifespan| lifespan.kitty_id != second);
+            <LifespanHeap<Test>>::push(Lifespan { kitty_id: H256::repeat_byte(9), end_time: 1 });
+
+            assert!(TemplateModule::rebuild_lifespan_heap(Origin::signed(1)).is_err());
+            assert_ok!(TemplateModule::rebuild_lifespan_heap(Origin::ROOT));
+
+            let mut expected = vec![lifespan_of(first), lifespan_of(second)];
+            expected.sort();
+            let mut rebuilt = <LifespanHeap<Test>>::to_vec();
+            rebuilt.sort();
+            assert_eq!(rebuilt, expected);
+            assert!(kitty_events().contains(&RawEvent::LifespanHeapRebuilt(2)));
+        });
+    }
+
+    #[test]
+    fn only_admin_can_gift_kitties() {
+        with_externalities(&mut new_test_ext(), || {
+            assert!(TemplateModule::gift_kitty(Origin::signed(1), 4, false).is_err());
+            assert_eq!(TemplateModule::owned_kitty_count(4), 0);
+
+            assert_ok!(TemplateModule::gift_kitty(Origin::ROOT, 4, false));
+            let kitty_id = TemplateModule::kitty_of_owner_by_index((4, 0));
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(4));
+            assert_eq!(TemplateModule::kitty(kitty_id).gen, 0);
+            assert!(kitty_events().contains(&RawEvent::Created(4, kitty_id)));
+            assert_eq!(Balances::free_balance(&4), 0);
+        });
+    }
+
+    #[test]
+    fn gifts_respect_the_ownership_cap() {
+        with_externalities(&mut new_test_ext(), || {
+            for _ in 0..MaxKittiesOwned::get() {
+                assert_ok!(TemplateModule::gift_kitty(Origin::ROOT, 4, false));
+            }
+            assert_noop!(TemplateModule::gift_kitty(Origin::ROOT, 4, false), "The recipient owns too many kitties");
+        });
+    }
+
+    #[test]
+    fn total_ever_created_survives_burns() {
+        with_externalities(&mut new_test_ext(), || {
+            let first = create_kitty_for(1);
+            let second = create_kitty_for(2);
+            assert_eq!(TemplateModule::total_ever_created(), 2);
+
+            let end_time = cmp::max(TemplateModule::kitty(first).lifetime.end_time,
+                TemplateModule::kitty(second).lifetime.end_time);
+            TemplateModule::remove_expired_kitties(end_time + 1, Weight::max_value());
+
+            assert_eq!(TemplateModule::all_kitties_count(), 0);
+            assert_eq!(TemplateModule::total_ever_created(), 2);
+        });
+    }
+
+    #[test]
+    fn set_price_enforces_the_listing_floor() {
+        with_externalities(&mut new_test_ext(), || {
+            MIN_LISTING_PRICE.with(|floor| floor.set(100));
+            let kitty_id = create_kitty_for(1);
+
+            assert_noop!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(99)),
+                "The price is below the listing floor");
+            assert_noop!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(0)),
+                "The price is below the listing floor");
+
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(100)));
+            assert_eq!(TemplateModule::kitty(kitty_id).price, Some(100));
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, None));
+            assert_eq!(TemplateModule::kitty(kitty_id).price, None);
+        });
+    }
+
+    #[test]
+    fn kitties_can_be_sold_in_another_asset() {
+        with_externalities(&mut new_test_ext(), || {
+            TestAssets::set_balance(7, 2, 1_000);
+            let kitty_id = create_kitty_for(1);
+
+            assert_ok!(TemplateModule::set_price_in_asset(Origin::signed(1), kitty_id, 500, 7));
+            assert_eq!(TemplateModule::price_asset_of(kitty_id), Some(7));
+            assert!(kitty_events().contains(&RawEvent::AssetPriceSet(1, kitty_id, 500, 7)));
+
+            assert_noop!(TemplateModule::buy_kitty(Origin::signed(3), kitty_id, 500, vec![], None),
+                "Not enough balance in the price asset");
+            assert_ok!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 500, vec![], None));
+
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(2));
+            assert_eq!(TestAssets::free_balance(7, &2), 500);
+            assert_eq!(TestAssets::free_balance(7, &99), 50);
+            assert_eq!(TestAssets::free_balance(7, &1), 450);
+            assert_eq!(Balances::free_balance(&1), 10_000);
+            assert_eq!(Balances::free_balance(&2), 10_000);
+            assert_eq!(TemplateModule::price_asset_of(kitty_id), None);
+        });
+    }
+
+    #[test]
+    fn asset_payments_are_checked_before_any_transfer() {
+        with_externalities(&mut new_test_ext(), || {
+            TestAssets::set_balance(7, 2, 1_000);
+            let kitty_id = create_kitty_for(1);
+
+            assert_noop!(TemplateModule::set_price_in_asset(Origin::signed(1), kitty_id, 500, 0),
+                "This asset does not exist");
+            assert_ok!(TemplateModule::set_price_in_asset(Origin::signed(1), kitty_id, 500, 7));
+
+            // The seller's leg is the last one, so the fee must not have moved either.
+            TestAssets::block(1);
+            assert_noop!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 500, vec![], None),
+                "Asset recipient is blocked");
+            assert_eq!(TestAssets::free_balance(7, &2), 1_000);
+            assert_eq!(TestAssets::free_balance(7, &99), 0);
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(1));
+        });
+    }
+
+    #[test]
+    fn native_set_price_forgets_the_asset() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_price_in_asset(Origin::signed(1), kitty_id, 500, 7));
+
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(500)));
+            assert_eq!(TemplateModule::price_asset_of(kitty_id), None);
+
+            assert_ok!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 500, vec![], None));
+            assert_eq!(Balances::free_balance(&2), 9_500);
+        });
+    }
+
+    #[test]
+    fn transfer_clears_the_listing() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(1_000)));
+            assert_eq!(TemplateModule::for_sale_count(), 1);
+
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, vec![]));
+
+            assert_eq!(TemplateModule::kitty(kitty_id).price, None);
+            assert_eq!(TemplateModule::for_sale_count(), 0);
+            assert_noop!(TemplateModule::buy_kitty(Origin::signed(3), kitty_id, 1_000, vec![], None),
+                "The cat you want to buy is not for sale");
+
+            assert_ok!(TemplateModule::set_price(Origin::signed(2), kitty_id, Some(2_000)));
+            assert_ok!(TemplateModule::buy_kitty(Origin::signed(3), kitty_id, 2_000, vec![], None));
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(3));
+        });
+    }
+
+    #[test]
+    fn burn_token_survives_inconsistent_counters() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+
+            // Nothing may be left behind, or the kitty would outlive its lifespan.
+            AllKittiesCount::put(0);
+            TemplateModule::burn_token(kitty_id);
+            assert!(!<Kitties<Test>>::exists(kitty_id));
+            assert_eq!(TemplateModule::owner_of(kitty_id), None);
+            assert_eq!(TemplateModule::owned_kitty_count(1), 0);
+
+            let kitty_id = create_kitty_for(1);
+            AllKittiesCount::put(1);
+            <OwnedKittiesCount<Test>>::insert(1, 0);
+            TemplateModule::burn_token(kitty_id);
+            assert!(!<Kitties<Test>>::exists(kitty_id));
+            assert_eq!(TemplateModule::owner_of(kitty_id), None);
+            assert_eq!(TemplateModule::all_kitties_count(), 0);
+        });
+    }
+
+    #[test]
+    fn young_kitties_cannot_be_listed_during_the_grace_period() {
+        with_externalities(&mut new_test_ext(), || {
+            SALE_GRACE_PERIOD.with(|grace| grace.set(100));
+            let kitty_id = create_kitty_for(1);
+            let listable_at = TemplateModule::kitty(kitty_id).lifetime.birth_time + 100;
+
+            set_mtp(listable_at - 1);
+            assert_noop!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(500)),
+                "This cat is too young to be listed");
+            assert_eq!(TemplateModule::capabilities(kitty_id).2, false);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, None));
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, vec![]));
+
+            set_mtp(listable_at);
+            assert_ok!(TemplateModule::set_price(Origin::signed(2), kitty_id, Some(500)));
+            assert_eq!(TemplateModule::kitty(kitty_id).price, Some(500));
+        });
+    }
+
+    #[test]
+    fn owner_history_tracks_the_last_owners() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_eq!(TemplateModule::owner_history(kitty_id), vec![1]);
+
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, vec![]));
+            assert_ok!(TemplateModule::transfer(Origin::signed(2), 3, kitty_id, vec![]));
+            assert_eq!(TemplateModule::owner_history(kitty_id), vec![1, 2, 3]);
+
+            assert_ok!(TemplateModule::transfer(Origin::signed(3), 1, kitty_id, vec![]));
+            assert_eq!(TemplateModule::owner_history(kitty_id), vec![2, 3, 1]);
+
+            TemplateModule::burn_token(kitty_id);
+            assert!(TemplateModule::owner_history(kitty_id).is_empty());
+        });
+    }
+
+    #[test]
+    fn kitties_created_in_one_block_are_distinct() {
+        with_externalities(&mut new_test_ext(), || {
+            let mut ids: Vec<H256> = (0..4).map(|_| create_kitty_for(1))
+                .chain((0..4).map(|_| create_kitty_for(2)))
+                .collect();
+            assert_eq!(CreatedThisBlock::get(), 8);
+
+            ids.sort();
+            ids.dedup();
+            assert_eq!(ids.len(), 8);
+
+            <TemplateModule as OnFinalize<u64>>::on_finalize(1);
+            assert_eq!(CreatedThisBlock::get(), 0);
+        });
+    }
+
+    #[test]
+    fn expiry_releases_open_offers() {
+        with_externalities(&mut new_test_ext(), || {
+            EXPIRY_MODE.with(|mode| mode.set(ExpiryMode::Freeze));
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::make_offer(Origin::signed(2), kitty_id, 300));
+            assert_ok!(TemplateModule::make_offer(Origin::signed(3), kitty_id, 400));
+            assert_eq!(Balances::reserved_balance(&2), 300);
+            assert_eq!(Balances::reserved_balance(&3), 400);
+
+            let kitty_end = TemplateModule::kitty(kitty_id).lifetime.end_time;
+            TemplateModule::remove_expired_kitties(kitty_end + 1, Weight::max_value());
+
+            assert!(TemplateModule::is_frozen(kitty_id));
+            assert!(TemplateModule::offers_for(kitty_id).is_empty());
+            assert_eq!(Balances::reserved_balance(&2), 0);
+            assert_eq!(Balances::reserved_balance(&3), 0);
+            assert_eq!(Balances::free_balance(&2), 10_000);
+            assert_eq!(Balances::free_balance(&3), 10_000);
+            assert!(kitty_events().contains(&RawEvent::OffersAbortedByExpiry(kitty_id, 2)));
+        });
+    }
+
+    #[test]
+    fn favorites_can_be_added_and_removed() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+
+            assert_noop!(TemplateModule::add_favorite(Origin::signed(2), H256::zero()), "This cat does not exist");
+            assert_ok!(TemplateModule::add_favorite(Origin::signed(2), kitty_id));
+            assert!(TemplateModule::is_favorite((2, kitty_id)));
+            assert_eq!(TemplateModule::favorite_count(2), 1);
+            assert_noop!(TemplateModule::add_favorite(Origin::signed(2), kitty_id), "This cat is already a favorite");
+            assert!(kitty_events().contains(&RawEvent::Favorited(2, kitty_id)));
+
+            assert_ok!(TemplateModule::remove_favorite(Origin::signed(2), kitty_id));
+            assert!(!TemplateModule::is_favorite((2, kitty_id)));
+            assert_eq!(TemplateModule::favorite_count(2), 0);
+            assert!(TemplateModule::favorited_by(kitty_id).is_empty());
+            assert_noop!(TemplateModule::remove_favorite(Origin::signed(2), kitty_id), "This cat is not a favorite");
+            assert!(kitty_events().contains(&RawEvent::Unfavorited(2, kitty_id)));
+        });
+    }
+
+    #[test]
+    fn favorites_are_bounded_per_account() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitties: Vec<H256> = (0..3).map(|_| create_kitty_for(1)).collect();
+
+            assert_ok!(TemplateModule::add_favorite(Origin::signed(2), kitties[0]));
+            assert_ok!(TemplateModule::add_favorite(Origin::signed(2), kitties[1]));
+            assert_noop!(TemplateModule::add_favorite(Origin::signed(2), kitties[2]), "Too many favorites");
+
+            assert_ok!(TemplateModule::remove_favorite(Origin::signed(2), kitties[0]));
+            assert_ok!(TemplateModule::add_favorite(Origin::signed(2), kitties[2]));
+        });
+    }
+
+    #[test]
+    fn favorites_are_bounded_per_kitty() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+
+            assert_ok!(TemplateModule::add_favorite(Origin::signed(2), kitty_id));
+            assert_ok!(TemplateModule::add_favorite(Origin::signed(3), kitty_id));
+            assert_noop!(TemplateModule::add_favorite(Origin::signed(4), kitty_id), "This cat has too many favorites");
+
+            assert_ok!(TemplateModule::remove_favorite(Origin::signed(3), kitty_id));
+            assert_ok!(TemplateModule::add_favorite(Origin::signed(4), kitty_id));
+            assert_eq!(TemplateModule::favorited_by(kitty_id), vec![2, 4]);
+        });
+    }
+
+    #[test]
+    fn burning_a_kitty_clears_its_favorites() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::add_favorite(Origin::signed(2), kitty_id));
+            assert_ok!(TemplateModule::add_favorite(Origin::signed(3), kitty_id));
+
+            TemplateModule::burn_token(kitty_id);
+
+            assert!(!TemplateModule::is_favorite((2, kitty_id)));
+            assert!(!TemplateModule::is_favorite((3, kitty_id)));
+            assert_eq!(TemplateModule::favorite_count(2), 0);
+            assert_eq!(TemplateModule::favorite_count(3), 0);
+            assert!(TemplateModule::favorited_by(kitty_id).is_empty());
+        });
+    }
+
+    #[test]
+    fn lifetimes_scale_with_generation() {
+        with_externalities(&mut new_test_ext(), || {
+            let dna = H256::zero();
+            let gen0 = TemplateModule::generate_lifetime(100, dna, 0).unwrap();
+            assert_eq!(TemplateModule::generate_lifetime(100, dna, 5), Ok(gen0.clone()));
+
+            GENERATION_LIFETIME_PERCENT.with(|percent| percent.set(50));
+            assert_eq!(TemplateModule::generate_lifetime(100, dna, 0), Ok(gen0.clone()));
+            let gen2 = TemplateModule::generate_lifetime(100, dna, 2).unwrap();
+            assert_eq!(gen2.maturity_time - 100, (gen0.maturity_time - 100) / 4);
+            assert_eq!(gen2.old_time - gen2.maturity_time, (gen0.old_time - gen0.maturity_time) / 4);
+            assert_eq!(gen2.end_time - gen2.old_time, (gen0.end_time - gen0.old_time) / 4);
+            // Lives shrink to nothing instead of looping through every generation.
+            assert_eq!(TemplateModule::generate_lifetime(100, dna, u64::max_value()).unwrap().end_time, 100);
+
+            GENERATION_LIFETIME_PERCENT.with(|percent| percent.set(200));
+            let gen1 = TemplateModule::generate_lifetime(100, dna, 1).unwrap();
+            assert_eq!(gen1.end_time - 100, (gen0.end_time - 100) * 2);
+            assert!(TemplateModule::generate_lifetime(100, dna, 64).is_err());
+        });
+    }
+
+    #[test]
+    fn kitty_details_bundles_the_individual_reads() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(TemplateModule::kitty_details(H256::zero()), None);
+
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_name(Origin::signed(1), kitty_id, b"tom".to_vec()));
+            let kitty = TemplateModule::kitty(kitty_id);
+            set_mtp(kitty.lifetime.maturity_time);
+
+            let details = TemplateModule::kitty_details(kitty_id).unwrap();
+            assert_eq!(details.kitty, kitty);
+            assert_eq!(Some(details.owner), TemplateModule::owner_of(kitty_id));
+            assert_eq!(details.life_stage, LifeStage::Maturity);
+            assert_eq!(Some(details.time_to_death), TemplateModule::time_to_death(kitty_id));
+            assert_eq!(details.name, TemplateModule::name_of(kitty_id));
+        });
+    }
+
+    #[test]
+    fn buy_many_buys_the_whole_batch() {
+        with_externalities(&mut new_test_ext(), || {
+            let first = create_kitty_for(1);
+            let second = create_kitty_for(1);
+            let third = create_kitty_for(3);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), first, Some(1_000)));
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), second, Some(2_000)));
+            assert_ok!(TemplateModule::set_price(Origin::signed(3), third, Some(3_000)));
+
+            assert_ok!(TemplateModule::buy_many(Origin::signed(2),
+                vec![(first, 1_000), (second, 2_500), (third, 3_000)]));
+
+            for kitty_id in &[first, second, third] {
+                assert_eq!(TemplateModule::owner_of(kitty_id), Some(2));
+                assert_eq!(TemplateModule::kitty(kitty_id).price, None);
+            }
+            assert_eq!(TemplateModule::for_sale_count(), 0);
+            assert_eq!(Balances::free_balance(&2), 4_000);
+            assert_eq!(Balances::free_balance(&1), 10_000 + 2_700);
+            assert_eq!(Balances::free_balance(&3), 10_000 + 2_700);
+            assert_eq!(Balances::free_balance(&99), 600);
+            assert!(kitty_events().contains(&RawEvent::Bought(2, 1, second, 2_000, vec![])));
+        });
+    }
+
+    #[test]
+    fn buy_many_rolls_back_on_any_failure() {
+        with_externalities(&mut new_test_ext(), || {
+            let first = create_kitty_for(1);
+            let second = create_kitty_for(1);
+            let unlisted = create_kitty_for(3);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), first, Some(1_000)));
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), second, Some(6_000)));
+
+            assert_noop!(TemplateModule::buy_many(Origin::signed(2), vec![(first, 1_000), (unlisted, 1_000)]),
+                "The cat you want to buy is not for sale");
+            assert_noop!(TemplateModule::buy_many(Origin::signed(2), vec![(first, 1_000), (second, 5_000)]),
+                "The cat you want to buy costs more than your max price");
+            assert_noop!(TemplateModule::buy_many(Origin::signed(2), vec![(first, 1_000), (first, 1_000)]),
+                "The same cat is in the batch twice");
+            assert_noop!(TemplateModule::buy_many(Origin::signed(2), vec![(first, 1_000); 4]),
+                "Too many purchases in one batch");
+
+            // Each is affordable on its own, not both together.
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), first, Some(5_000)));
+            assert!(TemplateModule::buy_many(Origin::signed(2), vec![(first, 5_000), (second, 6_000)]).is_err());
+            assert_eq!(Balances::free_balance(&2), 10_000);
+            assert_eq!(TemplateModule::owner_of(first), Some(1));
+            assert_eq!(TemplateModule::owner_of(second), Some(1));
+        });
+    }
+
+    #[test]
+    fn buy_many_checks_asset_transfers_before_the_native_withdraw() {
+        with_externalities(&mut new_test_ext(), || {
+            TestAssets::set_balance(7, 2, 1_000);
+            let native = create_kitty_for(1);
+            let in_asset = create_kitty_for(3);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), native, Some(1_000)));
+            assert_ok!(TemplateModule::set_price_in_asset(Origin::signed(3), in_asset, 500, 7));
+
+            // The asset sale comes after the native one, so it is only reached once
+            // the native price has been withdrawn.
+            TestAssets::block(3);
+            assert_noop!(TemplateModule::buy_many(Origin::signed(2), vec![(native, 1_000), (in_asset, 500)]),
+                "Asset recipient is blocked");
+            assert_eq!(Balances::free_balance(&2), 10_000);
+            assert_eq!(Balances::free_balance(&1), 10_000);
+            assert_eq!(TestAssets::free_balance(7, &2), 1_000);
+            assert_eq!(TemplateModule::owner_of(native), Some(1));
+            assert_eq!(TemplateModule::owner_of(in_asset), Some(3));
+        });
+    }
+
+    #[test]
+    fn buy_many_returns_the_native_payment_when_an_asset_leg_fails() {
+        with_externalities(&mut new_test_ext(), || {
+            TestAssets::set_balance(7, 2, 1_000);
+            let native = create_kitty_for(1);
+            let paid = create_kitty_for(3);
+            let broken = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), native, Some(1_000)));
+            assert_ok!(TemplateModule::set_price_in_asset(Origin::signed(3), paid, 200, 7));
+            assert_ok!(TemplateModule::set_price_in_asset(Origin::signed(1), broken, 300, 7));
+
+            // The third sale's seller leg fails only once the first asset leg went through.
+            TestAssets::break_transfers_to(1);
+            assert_eq!(TemplateModule::buy_many(Origin::signed(2), vec![(native, 1_000), (paid, 200), (broken, 300)]),
+                Err("Asset transfer failed"));
+
+            assert_eq!(Balances::free_balance(&2), 10_000);
+            assert_eq!(Balances::free_balance(&1), 10_000);
+            for (kitty_id, owner) in &[(native, 1), (paid, 3), (broken, 1)] {
+                assert_eq!(TemplateModule::owner_of(kitty_id), Some(*owner));
+            }
+            assert_eq!(TemplateModule::for_sale_count(), 3);
+        });
+    }
+
+    #[test]
+    fn expired_kitties_are_reaped_in_on_initialize() {
+        with_externalities(&mut new_test_ext(), || {
+            EXPIRY_MODE.with(|mode| mode.set(ExpiryMode::Burn));
+            <StorageVersion>::put(CURRENT_STORAGE_VERSION);
+            let first = create_kitty_for(1);
+            let second = create_kitty_for(2);
+            let survivor = create_kitty_for(3);
+            let end_time = cmp::max(TemplateModule::kitty(first).lifetime.end_time,
+                TemplateModule::kitty(second).lifetime.end_time);
+            <Kitties<Test>>::mutate(survivor, |kitty| kitty.lifetime.end_time = end_time + 100);
+            <LifespanHeap<Test>>::from_vec(vec![first, second, survivor].into_iter()
+                .map(|kitty_id| Lifespan { kitty_id, end_time: TemplateModule::kitty(kitty_id).lifetime.end_time })
+                .collect());
+
+            <TemplateModule as OnInitialize<u64>>::on_initialize(1);
+            assert_eq!(TemplateModule::all_kitties_count(), 3);
+
+            set_mtp(end_time + 1);
+            <TemplateModule as OnInitialize<u64>>::on_initialize(2);
+
+            assert!(!<Kitties<Test>>::exists(first));
+            assert!(!<Kitties<Test>>::exists(second));
+            assert!(<Kitties<Test>>::exists(survivor));
+            assert_eq!(<LifespanHeap<Test>>::len(), 1);
+        });
+    }
+
+    #[test]
+    fn on_initialize_leaves_kitties_beyond_the_reap_budget_for_later_blocks() {
+        with_externalities(&mut new_test_ext(), || {
+            EXPIRY_MODE.with(|mode| mode.set(ExpiryMode::Burn));
+            <StorageVersion>::put(CURRENT_STORAGE_VERSION);
+            let kitties: Vec<_> = (0..12).map(|i| create_kitty_for(i % 3 + 1)).collect();
+            let end_time = 1_000_000;
+            for kitty_id in &kitties {
+                <Kitties<Test>>::mutate(kitty_id, |kitty| kitty.lifetime.end_time = end_time);
+            }
+            TemplateModule::rebuild_lifespans();
+            // Reaping all twelve at once would take more than a quarter of the block.
+            let budget = MaximumBlockWeight::get() / REAP_BUDGET_DIVISOR;
+            assert!(TemplateModule::reap_weight_for(1).saturating_mul(12) > budget);
+
+            set_mtp(end_time + 1);
+            <TemplateModule as OnInitialize<u64>>::on_initialize(1);
+            let left = TemplateModule::all_kitties_count();
+            assert!(left > 0 && left < 12);
+            assert_eq!(<LifespanHeap<Test>>::len() as u64, left);
+
+            <TemplateModule as OnInitialize<u64>>::on_initialize(2);
+            assert_eq!(TemplateModule::all_kitties_count(), 0);
+            assert_eq!(<LifespanHeap<Test>>::len(), 0);
+        });
+    }
+
+    #[test]
+    fn expired_kitties_are_retired_to_the_expiry_destination() {
+        with_externalities(&mut new_test_ext(), || {
+            EXPIRY_MODE.with(|mode| mode.set(ExpiryMode::Burn));
+            EXPIRY_DESTINATION.with(|destination| destination.set(Some(50)));
+            let kitty_id = create_kitty_for(1);
+            let end_time = TemplateModule::kitty(kitty_id).lifetime.end_time;
+
+            set_mtp(end_time + 1);
+            TemplateModule::remove_expired_kitties(end_time + 1, Weight::max_value());
+
+            assert!(<Kitties<Test>>::exists(kitty_id));
+            assert!(TemplateModule::is_frozen(kitty_id));
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(50));
+            assert_eq!(TemplateModule::owned_kitty_count(1), 0);
+            assert_eq!(TemplateModule::owned_kitty_count(50), 1);
+            assert_eq!(TemplateModule::all_kitties_count(), 1);
+            assert!(TemplateModule::transfer(Origin::signed(50), 2, kitty_id, vec![]).is_err());
+        });
+    }
+
+    #[test]
+    fn expired_kitties_are_burned_without_an_expiry_destination() {
+        with_externalities(&mut new_test_ext(), || {
+            EXPIRY_MODE.with(|mode| mode.set(ExpiryMode::Burn));
+            let kitty_id = create_kitty_for(1);
+            let end_time = TemplateModule::kitty(kitty_id).lifetime.end_time;
+
+            TemplateModule::remove_expired_kitties(end_time + 1, Weight::max_value());
+
+            assert!(!<Kitties<Test>>::exists(kitty_id));
+            assert_eq!(TemplateModule::owner_of(kitty_id), None);
+            assert_eq!(TemplateModule::owned_kitty_count(50), 0);
+        });
+    }
+
+    #[test]
+    fn breeding_fee_grows_with_breed_count() {
+        with_externalities(&mut new_test_ext(), || {
+            BREED_FEE.with(|fee| fee.set((100, 50)));
+            let (first, second) = create_mature_pair(1);
+
+            assert_ok!(TemplateModule::breed_kitty(Origin::signed(1), first, second));
+            assert_eq!(Balances::free_balance(&1), 10_000 - 100);
+            assert_eq!(Balances::free_balance(&99), 100);
+
+            // Both parents have bred once.
+            assert_ok!(TemplateModule::breed_kitty(Origin::signed(1), first, second));
+            assert_eq!(Balances::free_balance(&1), 10_000 - 100 - 200);
+            assert_eq!(Balances::free_balance(&99), 300);
+            assert_eq!(TemplateModule::breed_fee(&TemplateModule::kitty(first), &TemplateModule::kitty(second)), 300);
+        });
+    }
+
+    #[test]
+    fn breeding_is_rejected_when_the_fee_cannot_be_paid() {
+        with_externalities(&mut new_test_ext(), || {
+            BREED_FEE.with(|fee| fee.set((9_000, 2_000)));
+            let (first, second) = create_mature_pair(1);
+
+            assert_ok!(TemplateModule::breed_kitty(Origin::signed(1), first, second));
+            assert_eq!(TemplateModule::owned_kitty_count(1), 3);
+
+            assert!(TemplateModule::breed_kitty(Origin::signed(1), first, second).is_err());
+            assert_eq!(TemplateModule::owned_kitty_count(1), 3);
+            assert_eq!(Balances::free_balance(&1), 1_000);
+            assert_eq!(TemplateModule::kitty(first).breed_count, 1);
+        });
+    }
+
+    #[test]
+    fn transfer_from_rejects_self_transfers() {
+        with_externalities(&mut new_test_ext(), || {
+            let first = create_kitty_for(1);
+            let second = create_kitty_for(1);
+
+            assert_noop!(TemplateModule::transfer_from(1, 1, first, vec![]), "Cannot transfer a kitty to its owner");
+            assert_eq!(TemplateModule::owned_kitty_count(1), 2);
+            assert_eq!(TemplateModule::kitty_of_owner_by_index((1, 0)), first);
+            assert_eq!(TemplateModule::kitty_of_owner_by_index((1, 1)), second);
+        });
+    }
+
+    #[test]
+    fn reindex_owned_repairs_an_accounts_enumeration() {
+        with_externalities(&mut new_test_ext(), || {
+            let first = create_kitty_for(1);
+            let second = create_kitty_for(1);
+            let other = create_kitty_for(2);
+
+            // Desync: a lost entry, a stale duplicate and a wrong count.
+            <OwnedKittiesArray<Test>>::insert((1, 0), second);
+            <OwnedKittiesArray<Test>>::insert((1, 2), other);
+            <OwnedKittiesCount<Test>>::insert(1, 3);
+
+            assert!(TemplateModule::reindex_owned(Origin::signed(1), 1).is_err());
+            assert_ok!(TemplateModule::reindex_owned(Origin::ROOT, 1));
+
+            assert_eq!(TemplateModule::owned_kitty_count(1), 2);
+            let mut owned: Vec<H256> = (0..2).map(|index| TemplateModule::kitty_of_owner_by_index((1, index))).collect();
+            owned.sort();
+            let mut expected = vec![first, second];
+            expected.sort();
+            assert_eq!(owned, expected);
+            assert_eq!(TemplateModule::kitty_of_owner_by_index((1, 2)), H256::zero());
+            for (index, kitty_id) in (0..2).map(|index| (index, TemplateModule::kitty_of_owner_by_index((1, index)))) {
+                assert_eq!(<OwnedKittiesIndex<Test>>::get(kitty_id), index);
+            }
+            assert!(kitty_events().contains(&RawEvent::OwnedKittiesReindexed(1, 2)));
+
+            // Still consistent enough to transfer out of.
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 3, first, vec![]));
+            assert_eq!(TemplateModule::kitty_of_owner_by_index((1, 0)), second);
+        });
+    }
+
+    #[test]
+    fn reindex_owned_clears_entries_past_an_understated_count() {
+        with_externalities(&mut new_test_ext(), || {
+            let first = create_kitty_for(1);
+            let other = create_kitty_for(2);
+
+            <OwnedKittiesArray<Test>>::insert((1, 1), other);
+            <OwnedKittiesArray<Test>>::insert((1, 2), other);
+            <OwnedKittiesCount<Test>>::insert(1, 0);
+
+            assert_ok!(TemplateModule::reindex_owned(Origin::ROOT, 1));
+
+            assert_eq!(TemplateModule::owned_kitty_count(1), 1);
+            assert_eq!(TemplateModule::kitty_of_owner_by_index((1, 0)), first);
+            assert!(!<OwnedKittiesArray<Test>>::exists((1, 1)));
+            assert!(!<OwnedKittiesArray<Test>>::exists((1, 2)));
+        });
+    }
+
+    #[test]
+    fn breeding_window_can_exclude_late_maturity() {
+        with_externalities(&mut new_test_ext(), || {
+            BREEDING_WINDOW.with(|window| window.set(TestWindow::FirstHalfOfMaturity));
+            let kitty = Kitty {
+                lifetime: Lifetime { birth_time: 100, maturity_time: 200, old_time: 300, end_time: 400 },
+                ..Default::default()
+            };
+            assert_eq!(TemplateModule::could_breed(199, &kitty), false);
+            assert_eq!(TemplateModule::could_breed(200, &kitty), true);
+            assert_eq!(TemplateModule::could_breed(249, &kitty), true);
+            assert_eq!(TemplateModule::could_breed(250, &kitty), false);
+            assert_eq!(TemplateModule::could_breed(299, &kitty), false);
+        });
+    }
+
+    #[test]
+    fn breeding_window_can_extend_into_early_oldness() {
+        with_externalities(&mut new_test_ext(), || {
+            BREEDING_WINDOW.with(|window| window.set(TestWindow::IntoEarlyOldness));
+            let kitty = Kitty {
+                lifetime: Lifetime { birth_time: 100, maturity_time: 200, old_time: 300, end_time: 400 },
+                ..Default::default()
+            };
+            assert_eq!(TemplateModule::could_breed(199, &kitty), false);
+            assert_eq!(TemplateModule::could_breed(200, &kitty), true);
+            assert_eq!(TemplateModule::could_breed(300, &kitty), true);
+            assert_eq!(TemplateModule::could_breed(349, &kitty), true);
+            assert_eq!(TemplateModule::could_breed(350, &kitty), false);
+
+            let (first, second) = create_mature_pair(1);
+            let lifetime = TemplateModule::kitty(first).lifetime;
+            <Kitties<Test>>::mutate(second, |kitty| kitty.lifetime = lifetime.clone());
+            set_mtp(lifetime.old_time);
+            assert_ok!(TemplateModule::breed_kitty(Origin::signed(1), first, second));
+        });
+    }
+
+    #[test]
+    fn breeding_announces_the_pedigree() {
+        with_externalities(&mut new_test_ext(), || {
+            let (first, second) = create_mature_pair(1);
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, second, Vec::new()));
+
+            assert_ok!(TemplateModule::breed_kitty(Origin::signed(2), first, second));
+
+            let child = TemplateModule::kitty_of_owner_by_index((2, 1));
+            assert_eq!(TemplateModule::kitty(child).gen, 1);
+            assert!(kitty_events().contains(&RawEvent::Created(2, child)));
+            assert!(kitty_events().contains(&RawEvent::Bred(2, first, second, child)));
+        });
+    }
+
+    #[test]
+    fn recount_owned_fixes_a_diverged_count() {
+        with_externalities(&mut new_test_ext(), || {
+            create_kitty_for(1);
+            create_kitty_for(1);
+            <OwnedKittiesCount<Test>>::insert(1, 5);
+
+            assert!(TemplateModule::recount_owned(Origin::signed(2), 1).is_err());
+            assert!(TemplateModule::recount_owned(Origin::signed(1), 1).is_err());
+            assert_ok!(TemplateModule::recount_owned(Origin::ROOT, 1));
+            assert_eq!(TemplateModule::owned_kitty_count(1), 2);
+            assert!(kitty_events().contains(&RawEvent::OwnedKittiesRecounted(1, 2)));
+
+            <OwnedKittiesCount<Test>>::insert(1, 0);
+            assert_ok!(TemplateModule::recount_owned(Origin::ROOT, 1));
+            assert_eq!(TemplateModule::owned_kitty_count(1), 2);
+            assert_eq!(TemplateModule::recount_owned_kitties(&3), 0);
+        });
+    }
+
+    #[test]
+    fn life_stage_from_times_test() {
+        let lifetime = Lifetime {
+            birth_time: 100u64,
+            maturity_time: 200,
+            old_time: 300,
+            end_time: 400,
+        };
+        assert_eq!(LifeStage::from_times(90, &lifetime), LifeStage::Invalid);
+        assert_eq!(LifeStage::from_times(100, &lifetime), LifeStage::Young);
+        assert_eq!(LifeStage::from_times(199, &lifetime), LifeStage::Young);
+        assert_eq!(LifeStage::from_times(200, &lifetime), LifeStage::Maturity);
+        assert_eq!(LifeStage::from_times(299, &lifetime), LifeStage::Maturity);
+        assert_eq!(LifeStage::from_times(300, &lifetime), LifeStage::Oldness);
+        assert_eq!(LifeStage::from_times(350, &lifetime), LifeStage::Oldness);
+        assert_eq!(LifeStage::from_times(400, &lifetime), LifeStage::Invalid);
+        assert_eq!(LifeStage::from_times(500, &lifetime), LifeStage::Invalid);
+        assert_eq!(LifeStage::from_times(250, &lifetime), TemplateModule::life_stage(250, &lifetime));
+    }
+
+    #[test]
+    fn dna_influence_on_lifetime_is_capped() {
+        with_externalities(&mut new_test_ext(), || {
+            let dna = H256::from_str(
+                "ffffff0000000000000000000000000000000000000000000000000000000000"
+            ).unwrap();
+            let raw = TemplateModule::generate_lifetime(0, dna, 0).unwrap();
+            assert_eq!(raw.maturity_time, (BASE_YOUNG_FACTOR as u64 + 255) * ONE_MINUTE);
+
+            FACTOR_CAPS.with(|caps| caps.set((10, 20, 30)));
+            let maturity_time = (BASE_YOUNG_FACTOR as u64 + 10) * ONE_MINUTE;
+            let old_time = maturity_time + (BASE_MATURITY_FACTOR as u64 + 20) * ONE_DAY;
+            let end_time = old_time + (BASE_OLDNESS_FACTOR as u64 + 30) * ONE_MINUTE;
+            assert_ok!(TemplateModule::generate_lifetime(0, dna, 0),
+                Lifetime { birth_time: 0, maturity_time, old_time, end_time });
+
+            // Bytes under the caps pass through unchanged.
+            let dna = H256::from_str(
+                "0203040000000000000000000000000000000000000000000000000000000000"
+            ).unwrap();
+            assert_eq!(TemplateModule::generate_lifetime(0, dna, 0).unwrap().maturity_time,
+                (BASE_YOUNG_FACTOR as u64 + 2) * ONE_MINUTE);
+        });
+    }
+
+    #[test]
+    fn listings_lapse_after_their_ttl() {
+        with_externalities(&mut new_test_ext(), || {
+            LISTING_TTL.with(|ttl| ttl.set(100));
+            let kitty_id = create_kitty_for(1);
+            set_mtp(10);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(500)));
+            assert_eq!(TemplateModule::listing_expires_at(kitty_id), Some(110));
+
+            set_mtp(109);
+            <TemplateModule as OnFinalize<u64>>::on_finalize(1);
+            assert_eq!(TemplateModule::kitty(kitty_id).price, Some(500));
+
+            set_mtp(110);
+            <TemplateModule as OnFinalize<u64>>::on_finalize(2);
+            assert_eq!(TemplateModule::kitty(kitty_id).price, None);
+            assert_eq!(TemplateModule::for_sale_count(), 0);
+            assert_eq!(TemplateModule::listing_expires_at(kitty_id), None);
+            assert!(kitty_events().contains(&RawEvent::ListingExpired(kitty_id)));
+        });
+    }
+
+    #[test]
+    fn relisting_refreshes_the_listing_ttl() {
+        with_externalities(&mut new_test_ext(), || {
+            LISTING_TTL.with(|ttl| ttl.set(100));
+            let kitty_id = create_kitty_for(1);
+            set_mtp(10);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(500)));
+            set_mtp(50);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(600)));
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(700)));
+            assert_eq!(<ListingExpiryHeap<Test>>::to_vec(), vec![Lifespan { kitty_id, end_time: 150 }]);
+
+            // The first listing's time passes without taking down the renewed one.
+            set_mtp(110);
+            <TemplateModule as OnFinalize<u64>>::on_finalize(1);
+            assert_eq!(TemplateModule::kitty(kitty_id).price, Some(700));
+            assert!(!kitty_events().contains(&RawEvent::ListingExpired(kitty_id)));
+
+            set_mtp(150);
+            <TemplateModule as OnFinalize<u64>>::on_finalize(2);
+            assert_eq!(TemplateModule::kitty(kitty_id).price, None);
+        });
+    }
+
+    #[test]
+    fn gen0_dna_comes_from_the_dna_generator() {
+        with_externalities(&mut new_test_ext(), || {
+            let plain = create_kitty_for(1);
+            assert_eq!(TemplateModule::kitty(plain).dna, plain);
+
+            FORCED_DNA_BYTE.with(|forced| forced.set(Some(0x42)));
+            for _ in 0..3 {
+                let kitty_id = create_kitty_for(2);
+                let kitty = TemplateModule::kitty(kitty_id);
+                assert_eq!(kitty.dna.as_ref()[3], 0x42);
+                assert_eq!(kitty.dna.as_ref()[4..], kitty_id.as_ref()[4..]);
+                assert_eq!(TemplateModule::phenotype(&kitty.dna).body_color[0], 0x42);
+            }
+        });
+    }
+
+    #[test]
+    fn encumbered_kitties_cannot_change_hands() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(500)));
+            assert_ok!(TemplateModule::make_offer(Origin::signed(3), kitty_id, 300));
+            TestScheduler::schedule(kitty_id);
+            assert!(TemplateModule::is_encumbered(kitty_id));
+            assert_eq!(TemplateModule::capabilities(kitty_id), (false, false, false));
+
+            assert_noop!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, vec![]),
+                "This cat has a pending operation");
+            assert_noop!(TemplateModule::safe_transfer(Origin::signed(1), 2, kitty_id),
+                "This cat has a pending operation");
+            assert_noop!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 500, vec![], None),
+                "This cat has a pending operation");
+            assert_noop!(TemplateModule::accept_offer(Origin::signed(1), kitty_id, 3),
+                "This cat has a pending operation");
+            assert_noop!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(600)),
+                "This cat has a pending operation");
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, None));
+
+            TestScheduler::cancel(kitty_id);
+            assert!(!TemplateModule::is_encumbered(kitty_id));
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, vec![]));
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(2));
+        });
+    }
+
+    #[test]
+    fn only_admin_can_force_burn() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            let other = create_kitty_for(2);
+            assert_ok!(TemplateModule::set_name(Origin::signed(1), kitty_id, b"Spam".to_vec()));
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(500)));
+            assert_ok!(TemplateModule::make_offer(Origin::signed(3), kitty_id, 300));
+            assert_ok!(TemplateModule::add_favorite(Origin::signed(2), kitty_id));
+
+            assert!(TemplateModule::force_burn(Origin::signed(2), kitty_id).is_err());
+            assert!(TemplateModule::force_burn(Origin::signed(1), kitty_id).is_err());
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(1));
+
+            assert_ok!(TemplateModule::force_burn(Origin::ROOT, kitty_id));
+            assert!(kitty_events().contains(&RawEvent::ForceBurned(kitty_id)));
+
+            assert!(!<Kitties<Test>>::exists(kitty_id));
+            assert_eq!(TemplateModule::owner_of(kitty_id), None);
+            assert_eq!(TemplateModule::all_kitties_count(), 1);
+            assert_eq!(TemplateModule::kitty_by_index(0), other);
+            assert_eq!(TemplateModule::owned_kitty_count(1), 0);
+            assert_eq!(TemplateModule::for_sale_count(), 0);
+            assert_eq!(TemplateModule::kitty_by_name(b"spam".to_vec()), None);
+            assert!(TemplateModule::offers_for(kitty_id).is_empty());
+            assert!(!TemplateModule::is_favorite((2, kitty_id)));
+            assert!(<LifespanHeap<Test>>::to_vec().iter().all(|lifespan| lifespan.kitty_id != kitty_id));
+
+            assert_noop!(TemplateModule::force_burn(Origin::ROOT, kitty_id), "This cat does not exist");
+        });
+    }
+
+    #[test]
+    fn reaping_reports_the_remaining_lifespans() {
+        with_externalities(&mut new_test_ext(), || {
+            let first = create_kitty_for(1);
+            let second = create_kitty_for(2);
+            <LifespanHeap<Test>>::push(Lifespan { kitty_id: H256::repeat_byte(9), end_time: u64::max_value() });
+            let end_time = TemplateModule::kitty(first).lifetime.end_time
+                .max(TemplateModule::kitty(second).lifetime.end_time);
+            let pruned = || kitty_events().into_iter()
+                .filter_map(|event| match event {
+                    RawEvent::LifespanHeapPruned(remaining) => Some(remaining),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            TemplateModule::remove_expired_kitties(end_time + 1, Weight::max_value());
+            assert_eq!(pruned(), vec![1]);
+
+            // Nothing reaped, nothing reported.
+            TemplateModule::remove_expired_kitties(end_time + 2, Weight::max_value());
+            assert_eq!(pruned(), vec![1]);
+        });
+    }
+
+    #[test]
+    fn accepting_a_swap_trades_both_kitties() {
+        with_externalities(&mut new_test_ext(), || {
+            let mine = create_kitty_for(1);
+            let theirs = create_kitty_for(2);
+
+            assert_noop!(TemplateModule::propose_swap(Origin::signed(1), theirs, mine, 2), "You do not own this cat");
+            assert_noop!(TemplateModule::propose_swap(Origin::signed(1), mine, theirs, 3),
+                "The counterparty does not own this cat");
+            assert_ok!(TemplateModule::propose_swap(Origin::signed(1), mine, theirs, 2));
+            assert!(kitty_events().contains(&RawEvent::SwapProposed(1, 2, 0)));
+            assert_eq!(Balances::reserved_balance(&1), SwapDeposit::get());
 
-        <KittyOwner<T>>::insert(&kitty_id, &to);
-        <OwnedKittiesIndex<T>>::insert(kitty_id, owned_kitty_count_to);
+            assert_noop!(TemplateModule::accept_swap(Origin::signed(1), 0), "This swap was not proposed to you");
+            assert_ok!(TemplateModule::accept_swap(Origin::signed(2), 0));
+            assert_eq!(Balances::reserved_balance(&1), 0);
+            assert_eq!(Balances::free_balance(&1), 10_000);
 
-        <OwnedKittiesArray<T>>::remove((from.clone(), new_owned_kitty_count_from));
-        <OwnedKittiesArray<T>>::insert((to.clone(), owned_kitty_count_to), kitty_id);
+            assert_eq!(TemplateModule::owner_of(mine), Some(2));
+            assert_eq!(TemplateModule::owner_of(theirs), Some(1));
+            assert_eq!(TemplateModule::kitty_of_owner_by_index((1, 0)), theirs);
+            assert_eq!(TemplateModule::kitty_of_owner_by_index((2, 0)), mine);
+            assert_eq!(TemplateModule::swap_proposal(0), None);
+            assert!(kitty_events().contains(&RawEvent::SwapAccepted(0)));
+            assert_noop!(TemplateModule::accept_swap(Origin::signed(2), 0), "There is no such swap proposal");
+        });
+    }
 
-        <OwnedKittiesCount<T>>::insert(&from, new_owned_kitty_count_from);
-        <OwnedKittiesCount<T>>::insert(&to, new_owned_kitty_count_to);
+    #[test]
+    fn swaps_fail_once_either_kitty_changed_hands() {
+        with_externalities(&mut new_test_ext(), || {
+            let mine = create_kitty_for(1);
+            let theirs = create_kitty_for(2);
+            assert_ok!(TemplateModule::propose_swap(Origin::signed(1), mine, theirs, 2));
+            assert_ok!(TemplateModule::propose_swap(Origin::signed(1), mine, theirs, 2));
 
-        Self::deposit_event(RawEvent::Transferred(from, to, kitty_id));
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 3, mine, vec![]));
+            assert_noop!(TemplateModule::accept_swap(Origin::signed(2), 0), "The proposer no longer owns their cat");
 
-        Ok(())
+            assert_ok!(TemplateModule::transfer(Origin::signed(3), 1, mine, vec![]));
+            assert_ok!(TemplateModule::transfer(Origin::signed(2), 3, theirs, vec![]));
+            assert_noop!(TemplateModule::accept_swap(Origin::signed(2), 1), "You no longer own this cat");
+
+            assert_eq!(TemplateModule::owner_of(mine), Some(1));
+            assert_eq!(TemplateModule::owner_of(theirs), Some(3));
+        });
     }
 
-    fn remove_expired_kitties(mtp: T::Moment) {
-        let stake = Lifespan {
-            kitty_id: T::Hash::default(),
-            end_time: mtp,
-        };
-        let expired_kitties = <LifespanHeap<T>>::pop_vec(&stake);
-        for lifespan in expired_kitties {
-            Self::burn_token(lifespan.kitty_id);
-        }
+    #[test]
+    fn swap_proposals_hold_a_deposit_and_need_tradable_kitties() {
+        with_externalities(&mut new_test_ext(), || {
+            let mine = create_kitty_for(1);
+            let theirs = create_kitty_for(2);
+
+            <Frozen<Test>>::insert(theirs, true);
+            assert_noop!(TemplateModule::propose_swap(Origin::signed(1), mine, theirs, 2), "This cat is frozen");
+            <Frozen<Test>>::remove(theirs);
+
+            assert_ok!(TemplateModule::propose_swap(Origin::signed(1), mine, theirs, 2));
+            assert_ok!(TemplateModule::propose_swap(Origin::signed(1), mine, theirs, 2));
+            assert_eq!(Balances::reserved_balance(&1), 2 * SwapDeposit::get());
+
+            assert_ok!(TemplateModule::cancel_swap(Origin::signed(2), 0));
+            assert_eq!(Balances::reserved_balance(&1), SwapDeposit::get());
+            assert_ok!(TemplateModule::cancel_swap(Origin::signed(1), 1));
+            assert_eq!(Balances::reserved_balance(&1), 0);
+            assert_eq!(Balances::free_balance(&1), 10_000);
+        });
     }
 
-    fn burn_token(kitty_id: T::Hash) {
-        // delete kitty
-        let count = Self::all_kitties_count();
-        if count == 0 {
-            // print err and return
-            runtime_io::print("burn_token(): There is no kitty.")
-        }
-        let last_kitty_index = count - 1;
-        let last_kitty_id = Self::kitty_by_index(last_kitty_index);
-        let kitty_index = <AllKittiesIndex<T>>::get(&kitty_id);
-        <AllKittiesArray<T>>::insert(kitty_index, &last_kitty_id);
-        <AllKittiesArray<T>>::remove(last_kitty_index);
-        <AllKittiesIndex<T>>::insert(last_kitty_id, kitty_index);
-        <AllKittiesIndex<T>>::remove(&kitty_id);
-        AllKittiesCount::put(last_kitty_index);
+    #[test]
+    fn reaping_a_kitty_cancels_its_swaps() {
+        with_externalities(&mut new_test_ext(), || {
+            EXPIRY_MODE.with(|mode| mode.set(ExpiryMode::Burn));
+            <StorageVersion>::put(CURRENT_STORAGE_VERSION);
+            let mine = create_kitty_for(1);
+            let theirs = create_kitty_for(2);
+            let other = create_kitty_for(3);
+            assert_ok!(TemplateModule::propose_swap(Origin::signed(1), mine, theirs, 2));
+            assert_ok!(TemplateModule::propose_swap(Origin::signed(3), other, theirs, 2));
+            assert_ok!(TemplateModule::propose_swap(Origin::signed(3), other, mine, 1));
+            assert_eq!(TemplateModule::swaps_for(theirs), vec![0, 1]);
 
-        <Kitties<T>>::remove(kitty_id);
+            let end_time = 1_000_000;
+            for kitty_id in &[mine, theirs, other] {
+                let late = if *kitty_id == theirs { 0 } else { 100 };
+                <Kitties<Test>>::mutate(kitty_id, |kitty| kitty.lifetime.end_time = end_time + late);
+            }
+            TemplateModule::rebuild_lifespans();
+            TemplateModule::remove_expired_kitties(end_time + 1, Weight::max_value());
 
-        // delete owner ship
-        let owner = Self::owner_of(&kitty_id);
-        if owner.is_none() {
-            // print err and return
-            runtime_io::print("burn_token(): No owner for this kitty")
-        }
-        let owner = owner.unwrap();
-        let owned_count = Self::owned_kitty_count(&owner);
-        if owned_count == 0 {
-            // print err and return
-            runtime_io::print("burn_token(): There is no ownership information")
-        }
-        let last_owned_index = owned_count - 1;
-        let last_owned_id = Self::kitty_of_owner_by_index((owner.clone(), last_owned_index));
-        let owned_index = <OwnedKittiesIndex<T>>::get(&kitty_id);
-        <OwnedKittiesArray<T>>::insert((owner.clone(), owned_index), &last_owned_id);
-        <OwnedKittiesArray<T>>::remove((owner.clone(), last_owned_index));
-        <OwnedKittiesIndex<T>>::insert(last_owned_id, owned_index);
-        <OwnedKittiesIndex<T>>::remove(&kitty_id);
-        <OwnedKittiesCount<T>>::insert(owner, last_owned_index);
+            assert!(!<Kitties<Test>>::exists(theirs));
+            assert_eq!(TemplateModule::swap_proposal(0), None);
+            assert_eq!(TemplateModule::swap_proposal(1), None);
+            assert!(kitty_events().contains(&RawEvent::SwapCancelled(0)));
+            assert!(kitty_events().contains(&RawEvent::SwapCancelled(1)));
+            assert_eq!(Balances::reserved_balance(&1), 0);
+            assert_eq!(Balances::reserved_balance(&3), SwapDeposit::get());
+            assert!(!<SwapsByKitty<Test>>::exists(theirs));
+            assert_eq!(TemplateModule::swaps_for(mine), vec![2]);
+            assert_eq!(TemplateModule::swaps_for(other), vec![2]);
 
-        <KittyOwner<T>>::remove(kitty_id);
+            assert_ok!(TemplateModule::accept_swap(Origin::signed(1), 2));
+            assert_eq!(Balances::reserved_balance(&3), 0);
+            assert!(TemplateModule::swaps_for(mine).is_empty());
+        });
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn life_factors_apply_to_kitties_born_afterwards() {
+        with_externalities(&mut new_test_ext(), || {
+            FACTOR_CAPS.with(|caps| caps.set((0, 0, 0)));
+            let phases = |kitty_id| {
+                let lifetime = TemplateModule::kitty(kitty_id).lifetime;
+                (lifetime.maturity_time - lifetime.birth_time,
+                    lifetime.old_time - lifetime.maturity_time,
+                    lifetime.end_time - lifetime.old_time)
+            };
+            let before = create_kitty_for(1);
+            let base = (BASE_YOUNG_FACTOR as u64 * ONE_MINUTE, BASE_MATURITY_FACTOR as u64 * ONE_DAY,
+                BASE_OLDNESS_FACTOR as u64 * ONE_MINUTE);
+            assert_eq!(phases(before), base);
 
-    use runtime_io::with_externalities;
-    use primitives::{H256, Blake2Hasher};
-    use support::{impl_outer_origin, assert_ok, parameter_types};
-    use sr_primitives::{traits::{BlakeTwo256, IdentityLookup}, testing::Header};
-    use sr_primitives::weights::Weight;
-    use sr_primitives::Perbill;
-    use timestamp;
-    use std::str::FromStr;
+            assert_noop!(TemplateModule::set_life_factors(Origin::ROOT, 0, 20, 40),
+                "The young factor is below its base");
+            assert_noop!(TemplateModule::set_life_factors(Origin::ROOT, 50, BASE_MATURITY_FACTOR - 1, 40),
+                "The maturity factor is below its base");
+            assert_noop!(TemplateModule::set_life_factors(Origin::ROOT, 50, 20, 0),
+                "The oldness factor is below its base");
+            assert!(TemplateModule::set_life_factors(Origin::signed(1), 50, 20, 40).is_err());
+            assert_ok!(TemplateModule::set_life_factors(Origin::ROOT, 50, 20, 40));
+            assert!(kitty_events().contains(&RawEvent::LifeFactorsSet(50, 20, 40)));
+            assert_eq!(TemplateModule::young_factor(), 50);
 
-    impl_outer_origin! {
-      pub enum Origin for Test {}
+            let after = create_kitty_for(2);
+            assert_eq!(phases(after), (50 * ONE_MINUTE, 20 * ONE_DAY, 40 * ONE_MINUTE));
+            assert_eq!(phases(before), base);
+        });
     }
 
-    // For testing the module, we construct most of a mock runtime. This means
-    // first constructing a configuration type (`Test`) which `impl`s each of the
-    // configuration traits of modules we want to use.
-    #[derive(Clone, Eq, PartialEq)]
-    pub struct Test;
-    parameter_types! {
-      pub const BlockHashCount: u64 = 250;
-      pub const MaximumBlockWeight: Weight = 1024;
-      pub const MaximumBlockLength: u32 = 2 * 1024;
-      pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+    #[test]
+    fn dead_kitties_awaiting_reaping_cannot_be_traded() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(500)));
+            let kitty = TemplateModule::kitty(kitty_id);
+            let lifetime = kitty.lifetime.clone();
+
+            assert!(TemplateModule::is_alive(lifetime.birth_time, &kitty));
+            assert!(!TemplateModule::is_alive(lifetime.birth_time - 1, &kitty));
+            assert!(TemplateModule::is_alive(lifetime.old_time, &kitty));
+            assert!(!TemplateModule::could_transfer(lifetime.old_time, &kitty));
+            assert!(TemplateModule::is_alive(lifetime.end_time - 1, &kitty));
+            assert!(!TemplateModule::is_alive(lifetime.end_time, &kitty));
+
+            set_mtp(lifetime.end_time);
+            assert_noop!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 500, vec![], None),
+                "This cat is no longer alive");
+            assert_noop!(TemplateModule::make_offer(Origin::signed(2), kitty_id, 300),
+                "This cat is no longer alive");
+
+            // Old kitties are alive but no longer for sale, even if still listed.
+            set_mtp(lifetime.end_time - 1);
+            assert_ok!(TemplateModule::make_offer(Origin::signed(2), kitty_id, 300));
+            assert_noop!(TemplateModule::buy_kitty(Origin::signed(3), kitty_id, 500, vec![], None),
+                "This cat is not in the life stage that can be transferred");
+
+            set_mtp(lifetime.old_time - 1);
+            assert_ok!(TemplateModule::buy_kitty(Origin::signed(3), kitty_id, 500, vec![], None));
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(3));
+        });
     }
 
-    impl system::Trait for Test {
-        type Origin = Origin;
-        type Call = ();
-        type Index = u64;
-        type BlockNumber = u64;
-        type Hash = H256;
-        type Hashing = BlakeTwo256;
-        type AccountId = u64;
-        type Lookup = IdentityLookup<Self::AccountId>;
-        type WeightMultiplierUpdate = ();
-        type Header = Header;
-        type Event = ();
-        type BlockHashCount = BlockHashCount;
-        type MaximumBlockWeight = MaximumBlockWeight;
-        type MaximumBlockLength = MaximumBlockLength;
-        type AvailableBlockRatio = AvailableBlockRatio;
-        type Version = ();
+    #[test]
+    fn soulbound_kitties_stay_with_their_owner() {
+        with_externalities(&mut new_test_ext(), || {
+            let mate = create_kitty_for(1);
+            assert_ok!(TemplateModule::gift_kitty(Origin::ROOT, 1, true));
+            let kitty_id = TemplateModule::kitty_of_owner_by_index((1, 1));
+            assert!(TemplateModule::is_soulbound(kitty_id));
+            assert!(!TemplateModule::is_soulbound(mate));
+            assert_eq!(TemplateModule::capabilities(kitty_id), (false, false, false));
+            assert_eq!(TemplateModule::capabilities(mate), (false, true, true));
+
+            assert_noop!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, vec![]), "This cat is soulbound");
+            assert_noop!(TemplateModule::safe_transfer(Origin::signed(1), 2, kitty_id), "This cat is soulbound");
+            assert_noop!(TemplateModule::set_price(Origin::signed(1), kitty_id, Some(500)), "This cat is soulbound");
+            assert_noop!(TemplateModule::set_price_in_asset(Origin::signed(1), kitty_id, 500, 7),
+                "This cat is soulbound");
+            assert_noop!(TemplateModule::make_offer(Origin::signed(2), kitty_id, 300), "This cat is soulbound");
+            assert_noop!(TemplateModule::buy_kitty(Origin::signed(2), kitty_id, 500, vec![], None),
+                "This cat is soulbound");
+
+            // Breeding is still allowed, and the offspring is free to trade.
+            let lifetime = TemplateModule::kitty(kitty_id).lifetime;
+            <Kitties<Test>>::mutate(mate, |kitty| kitty.lifetime = lifetime.clone());
+            set_mtp(lifetime.maturity_time);
+            assert_eq!(TemplateModule::capabilities(kitty_id), (true, false, false));
+            assert_ok!(TemplateModule::breed_kitty(Origin::signed(1), kitty_id, mate));
+            let child = TemplateModule::kitty_of_owner_by_index((1, 2));
+            assert!(!TemplateModule::is_soulbound(child));
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, child, vec![]));
+        });
     }
 
-    impl balances::Trait for Test {
-        type Balance = u64;
-        type OnFreeBalanceZero = ();
-        type OnNewAccount = ();
-        type TransactionPayment = ();
-        type TransferPayment = ();
-        type DustRemoval = ();
-        type Event = ();
-        type ExistentialDeposit = ();
-        type TransferFee = ();
-        type CreationFee = ();
-        type TransactionBaseFee = ();
-        type TransactionByteFee = ();
-        type WeightToFee = ();
+    #[test]
+    fn burning_purges_every_auxiliary_map() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::set_name(Origin::signed(1), kitty_id, b"Tom".to_vec()));
+            assert_ok!(TemplateModule::equip_accessory(Origin::signed(1), kitty_id, 7));
+            assert_ok!(TemplateModule::set_price_in_asset(Origin::signed(1), kitty_id, 500, 3));
+            assert_ok!(TemplateModule::make_offer(Origin::signed(2), kitty_id, 300));
+            assert_ok!(TemplateModule::add_favorite(Origin::signed(3), kitty_id));
+            <Experience<Test>>::insert(kitty_id, 5);
+            <Fancy<Test>>::insert(kitty_id, 1);
+            <Soulbound<Test>>::insert(kitty_id, true);
+
+            TemplateModule::burn_token(kitty_id);
+
+            assert!(!<Names<Test>>::exists(kitty_id));
+            assert_eq!(TemplateModule::kitty_by_name(b"tom".to_vec()), None);
+            assert!(!<Accessories<Test>>::exists(kitty_id));
+            assert!(!<Experience<Test>>::exists(kitty_id));
+            assert!(!<Fancy<Test>>::exists(kitty_id));
+            assert!(!<Soulbound<Test>>::exists(kitty_id));
+            assert!(!<Frozen<Test>>::exists(kitty_id));
+            assert!(!<OwnerHistory<Test>>::exists(kitty_id));
+            assert!(!<ForSaleIndex<Test>>::exists(kitty_id));
+            assert_eq!(TemplateModule::for_sale_count(), 0);
+            assert!(!<PriceAsset<Test>>::exists(kitty_id));
+            assert!(!<ListingExpiresAt<Test>>::exists(kitty_id));
+            assert!(!<Offers<Test>>::exists(kitty_id));
+            assert_eq!(Balances::reserved_balance(&2), 0);
+            assert!(!<FavoritedBy<Test>>::exists(kitty_id));
+            assert!(!TemplateModule::is_favorite((3, kitty_id)));
+            assert_eq!(TemplateModule::favorite_count(3), 0);
+        });
     }
 
-    impl timestamp::Trait for Test {
-        type Moment = u64;
-        type OnTimestampSet = ();
-        type MinimumPeriod = ();
+    #[test]
+    fn do_create_returns_the_new_kitty_id() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = TemplateModule::do_create(1).unwrap();
+            assert!(<Kitties<Test>>::exists(kitty_id));
+            assert_eq!(TemplateModule::kitty(kitty_id).id, kitty_id);
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(1));
+            assert_eq!(TemplateModule::kitty_of_owner_by_index((1, 0)), kitty_id);
+            assert!(kitty_events().contains(&RawEvent::Created(1, kitty_id)));
+
+            CREATOR_GATING.with(|gating| gating.set(true));
+            assert_eq!(TemplateModule::do_create(1), Err("This account may not create kitties"));
+        });
     }
 
-    impl mtp::Trait for Test {}
+    #[test]
+    fn reaping_stops_at_the_weight_budget() {
+        with_externalities(&mut new_test_ext(), || {
+            EXPIRY_MODE.with(|mode| mode.set(ExpiryMode::Burn));
+            <StorageVersion>::put(CURRENT_STORAGE_VERSION);
+            let kitties: Vec<_> = (1..=4).map(|owner| create_kitty_for(owner % 3 + 1)).collect();
+            let end_time = 1_000_000;
+            for kitty_id in &kitties {
+                <Kitties<Test>>::mutate(kitty_id, |kitty| kitty.lifetime.end_time = end_time);
+            }
+            TemplateModule::rebuild_lifespans();
+
+            assert_eq!(TemplateModule::remove_expired_kitties(end_time - 1, Weight::max_value()), 0);
+            assert_eq!(TemplateModule::remove_expired_kitties(end_time + 1, 0), 0);
+            assert_eq!(TemplateModule::all_kitties_count(), 4);
 
-    impl Trait for Test {
-        type Event = ();
+            // Enough for two pops, one short of a third.
+            let two = TemplateModule::reap_weight_for(4) + TemplateModule::reap_weight_for(3);
+            let budget = two + TemplateModule::reap_weight_for(2) - 1;
+            assert_eq!(TemplateModule::remove_expired_kitties(end_time + 1, budget), two);
+            assert_eq!(TemplateModule::all_kitties_count(), 2);
+            assert_eq!(<LifespanHeap<Test>>::len(), 2);
+
+            let rest = TemplateModule::reap_weight_for(2) + TemplateModule::reap_weight_for(1);
+            assert_eq!(TemplateModule::remove_expired_kitties(end_time + 1, Weight::max_value()), rest);
+            assert_eq!(TemplateModule::all_kitties_count(), 0);
+        });
     }
 
-    type TemplateModule = Module<Test>;
+    #[test]
+    fn old_kitties_can_only_be_moved_by_rescue_transfer() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            let lifetime = TemplateModule::kitty(kitty_id).lifetime;
 
-    // This function basically just builds a genesis storage key/value store according to
-    // our desired mockup.
-    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
-        system::GenesisConfig::default().build_storage::<Test>().unwrap().into()
+            set_mtp(lifetime.old_time);
+            assert_noop!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, vec![]),
+                "This cat is not in the life stage that can be transferred");
+            assert_noop!(TemplateModule::rescue_transfer(Origin::signed(2), 3, kitty_id), "You do not own this kitty");
+            assert_ok!(TemplateModule::rescue_transfer(Origin::signed(1), 2, kitty_id));
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(2));
+
+            set_mtp(lifetime.end_time);
+            assert_noop!(TemplateModule::transfer(Origin::signed(2), 1, kitty_id, vec![]),
+                "This cat is not in the life stage that can be transferred");
+            assert_noop!(TemplateModule::rescue_transfer(Origin::signed(2), 1, kitty_id),
+                "This cat is no longer alive");
+        });
     }
 
     #[test]
-    fn generate_lifetime_test() {
+    fn dna_summary_packs_the_phenotype() {
         with_externalities(&mut new_test_ext(), || {
-            let dna = H256::from_str(
-                "0000000000000000000000000000000000000000000000000000000000000000"
-            ).unwrap();
-            let birth_time: u64 = 100;
-            let maturity_time = birth_time + BASE_YOUNG_FACTOR as u64 * ONE_MINUTE;
-            let old_time = maturity_time + BASE_MATURITY_FACTOR as u64 * ONE_DAY;
-            let end_time = old_time + BASE_OLDNESS_FACTOR as u64 * ONE_MINUTE;
-            assert_ok!(TemplateModule::generate_lifetime(100, dna),
-                Lifetime{ birth_time, maturity_time, old_time, end_time, });
+            let mut dna = H256::default();
+            dna.as_mut()[3..8].copy_from_slice(&[0xff, 0x10, 0x80, 0x0e, 0x05]);
+            // Pattern 6, eye shape 1, red and blue bright.
+            assert_eq!(TemplateModule::dna_summary(&dna), 0b1010_1110);
+            dna.as_mut()[20] = 0xff;
+            assert_eq!(TemplateModule::dna_summary(&dna), 0b1010_1110);
 
-            let dna = H256::from_str(
-                "0203040000000000000000000000000000000000000000000000000000000000"
-            ).unwrap();
-            let birth_time: u64 = 100;
-            let maturity_time = birth_time + (BASE_YOUNG_FACTOR + 2) as u64 * ONE_MINUTE;
-            let old_time = maturity_time + (BASE_MATURITY_FACTOR + 3) as u64 * ONE_DAY;
-            let end_time = old_time + (BASE_OLDNESS_FACTOR + 4) as u64 * ONE_MINUTE;
-            assert_ok!(TemplateModule::generate_lifetime(100, dna),
-                Lifetime{ birth_time, maturity_time, old_time, end_time, });
+            create_kitty_for(1);
+            assert!(kitty_events().iter().all(|event| match event {
+                RawEvent::DnaSummary(..) => false,
+                _ => true,
+            }));
+
+            EMIT_DNA_SUMMARY.with(|emit| emit.set(true));
+            let kitty_id = create_kitty_for(2);
+            let summary = TemplateModule::dna_summary(&TemplateModule::kitty(kitty_id).dna);
+            assert!(kitty_events().contains(&RawEvent::DnaSummary(kitty_id, summary)));
         });
     }
 
     #[test]
-    fn life_stage_test() {
+    fn breeding_needs_one_of_the_parents() {
         with_externalities(&mut new_test_ext(), || {
-            let lifetime = Lifetime {
-                birth_time: 100,
-                maturity_time: 200,
-                old_time: 300,
-                end_time: 400,
-            };
-            assert_eq!(TemplateModule::life_stage(90, &lifetime), LifeStage::Invalid);
-            assert_eq!(TemplateModule::life_stage(100, &lifetime), LifeStage::Young);
-            assert_eq!(TemplateModule::life_stage(199, &lifetime), LifeStage::Young);
-            assert_eq!(TemplateModule::life_stage(200, &lifetime), LifeStage::Maturity);
-            assert_eq!(TemplateModule::life_stage(299, &lifetime), LifeStage::Maturity);
-            assert_eq!(TemplateModule::life_stage(300, &lifetime), LifeStage::Oldness);
-            assert_eq!(TemplateModule::life_stage(350, &lifetime), LifeStage::Oldness);
-            assert_eq!(TemplateModule::life_stage(400, &lifetime), LifeStage::Invalid);
-            assert_eq!(TemplateModule::life_stage(500, &lifetime), LifeStage::Invalid);
+            let (first, second) = create_mature_pair(1);
+
+            assert_noop!(TemplateModule::breed_kitty(Origin::signed(3), first, second),
+                "You do not own either cat");
+
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 3, second, Vec::new()));
+            assert_ok!(TemplateModule::breed_kitty(Origin::signed(3), first, second));
+            assert_eq!(TemplateModule::owned_kitty_count(3), 2);
+            assert_eq!(TemplateModule::kitty(first).breed_count, 1);
         });
     }
 
     #[test]
-    fn life_stage_limit_test() {
+    fn breeding_fails_cleanly_on_generation_overflow() {
         with_externalities(&mut new_test_ext(), || {
-            let kitty = Kitty {
-                id: H256::default(),
-                dna: H256::default(),
-                price: 0,
-                gen: 0,
-                lifetime: Lifetime {
-                    birth_time: 100,
-                    maturity_time: 200,
-                    old_time: 300,
-                    end_time: 400,
-                },
+            let (first, second) = create_mature_pair(1);
+            <Kitties<Test>>::mutate(first, |kitty| kitty.gen = u64::max_value());
+            <Kitties<Test>>::mutate(second, |kitty| kitty.gen = 3);
+
+            assert_noop!(TemplateModule::breed_kitty(Origin::signed(1), first, second),
+                "Overflow calculating the generation of the new kitty");
+            assert_noop!(TemplateModule::breed_kitty(Origin::signed(1), second, first),
+                "Overflow calculating the generation of the new kitty");
+            assert_eq!(TemplateModule::owned_kitty_count(1), 2);
+        });
+    }
+
+    #[test]
+    fn for_sale_count_tracks_listings() {
+        with_externalities(&mut new_test_ext(), || {
+            LISTING_TTL.with(|ttl| ttl.set(100));
+            let sold = create_kitty_for(1);
+            let delisted = create_kitty_for(1);
+            let lapsed = create_kitty_for(1);
+            assert_eq!(TemplateModule::for_sale_count(), 0);
+
+            for kitty_id in &[sold, delisted, lapsed] {
+                assert_ok!(TemplateModule::set_price(Origin::signed(1), *kitty_id, Some(500)));
+            }
+            assert_eq!(TemplateModule::for_sale_count(), 3);
+            // Repricing keeps a single entry.
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), sold, Some(400)));
+            assert_eq!(TemplateModule::for_sale_count(), 3);
+
+            assert_ok!(TemplateModule::buy_kitty(Origin::signed(2), sold, 400, vec![], None));
+            assert_eq!(TemplateModule::for_sale_count(), 2);
+
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), delisted, None));
+            assert_eq!(TemplateModule::for_sale_count(), 1);
+            assert_ok!(TemplateModule::set_price(Origin::signed(1), delisted, None));
+            assert_eq!(TemplateModule::for_sale_count(), 1);
+
+            let mtp = TemplateModule::listing_expires_at(lapsed).unwrap();
+            TemplateModule::expire_listings(mtp);
+            assert_eq!(TemplateModule::for_sale_count(), 0);
+
+            // A stray index entry with the count already at zero doesn't wrap it.
+            <ForSaleIndex<Test>>::insert(delisted, 0);
+            TemplateModule::index_listing(delisted, false);
+            assert_eq!(TemplateModule::for_sale_count(), 0);
+        });
+    }
+
+    #[test]
+    fn moving_the_last_or_a_middle_kitty_keeps_enumerations_consistent() {
+        with_externalities(&mut new_test_ext(), || {
+            let owned = |account: u64| (0..TemplateModule::owned_kitty_count(account))
+                .map(|index| TemplateModule::kitty_of_owner_by_index((account, index)))
+                .collect::<Vec<_>>();
+            let assert_consistent = |account: u64| {
+                for (index, kitty_id) in owned(account).into_iter().enumerate() {
+                    assert_eq!(<OwnedKittiesIndex<Test>>::get(kitty_id), index as u64);
+                    assert_eq!(TemplateModule::owner_of(kitty_id), Some(account));
+                }
+                let count = TemplateModule::owned_kitty_count(account);
+                assert!(!<OwnedKittiesArray<Test>>::exists((account, count)));
             };
+            let kitties: Vec<_> = (0..4).map(|_| create_kitty_for(1)).collect();
 
-            assert_eq!(TemplateModule::could_breed(199, &kitty), false);
-            assert_eq!(TemplateModule::could_breed(200, &kitty), true);
-            assert_eq!(TemplateModule::could_breed(210, &kitty), true);
-            assert_eq!(TemplateModule::could_breed(300, &kitty), false);
-            assert_eq!(TemplateModule::could_breed(310, &kitty), false);
+            // Last index: nothing to swap.
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitties[3], vec![]));
+            assert_eq!(owned(1), vec![kitties[0], kitties[1], kitties[2]]);
+            assert_eq!(owned(2), vec![kitties[3]]);
 
-            assert_eq!(TemplateModule::could_transfer(99, &kitty), false);
-            assert_eq!(TemplateModule::could_transfer(100, &kitty), true);
-            assert_eq!(TemplateModule::could_transfer(110, &kitty), true);
-            assert_eq!(TemplateModule::could_transfer(200, &kitty), true);
-            assert_eq!(TemplateModule::could_transfer(210, &kitty), true);
-            assert_eq!(TemplateModule::could_transfer(300, &kitty), false);
-            assert_eq!(TemplateModule::could_transfer(400, &kitty), false);
+            // Middle index: the last kitty takes its place.
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitties[0], vec![]));
+            assert_eq!(owned(1), vec![kitties[2], kitties[1]]);
+            assert_eq!(owned(2), vec![kitties[3], kitties[0]]);
+
+            assert_consistent(1);
+            assert_consistent(2);
+        });
+    }
+
+    #[test]
+    fn migration_is_spread_over_blocks_and_blocks_calls_meanwhile() {
+        with_externalities(&mut new_test_ext(), || {
+            let total = MIGRATION_BATCH + 50;
+            for index in 0..total {
+                let kitty_id = H256::repeat_byte(index as u8 + 1);
+                <Kitties<Test>>::insert(kitty_id, Kitty { id: kitty_id, price: Some(10), ..Default::default() });
+                <AllKittiesArray<Test>>::insert(index, kitty_id);
+            }
+            <AllKittiesCount>::put(total);
+            <StorageVersion>::put(4);
+
+            <TemplateModule as OnInitialize<u64>>::on_initialize(1);
+            assert!(TemplateModule::migrating());
+            assert_eq!(TemplateModule::storage_version(), 4);
+            assert_eq!(TemplateModule::migration_cursor(), MIGRATION_BATCH);
+            assert_eq!(TemplateModule::for_sale_count(), MIGRATION_BATCH);
+            assert_noop!(TemplateModule::create_kitty(Origin::signed(1)), "Kitties storage is being migrated");
+
+            <TemplateModule as OnInitialize<u64>>::on_initialize(2);
+            assert!(!TemplateModule::migrating());
+            assert_eq!(TemplateModule::storage_version(), CURRENT_STORAGE_VERSION);
+            assert_eq!(TemplateModule::migration_cursor(), 0);
+            assert_eq!(TemplateModule::for_sale_count(), total);
+            assert_ok!(TemplateModule::create_kitty(Origin::signed(1)));
+        });
+    }
+
+    #[test]
+    fn accepting_an_unbacked_offer_leaves_it_in_place() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::make_offer(Origin::signed(2), kitty_id, 1_000));
+            <Balances as ReservableCurrency<_>>::unreserve(&2, 600);
+
+            assert_noop!(TemplateModule::accept_offer(Origin::signed(1), kitty_id, 2),
+                "The offer is no longer backed by reserved funds");
+            assert_eq!(TemplateModule::offers_for(kitty_id).len(), 1);
+            assert_eq!(TemplateModule::owner_of(kitty_id), Some(1));
+            assert_eq!(Balances::reserved_balance(&2), 400);
+        });
+    }
+
+    #[test]
+    fn receiving_a_kitty_refunds_your_own_offer_on_it() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            assert_ok!(TemplateModule::make_offer(Origin::signed(2), kitty_id, 300));
+            assert_ok!(TemplateModule::make_offer(Origin::signed(3), kitty_id, 400));
+
+            assert_ok!(TemplateModule::transfer(Origin::signed(1), 2, kitty_id, vec![]));
+
+            assert_eq!(Balances::reserved_balance(&2), 0);
+            assert_eq!(Balances::free_balance(&2), 10_000);
+            assert!(kitty_events().contains(&RawEvent::OfferCancelled(2, kitty_id)));
+            assert_eq!(TemplateModule::offers_for(kitty_id).len(), 1);
+            assert_noop!(TemplateModule::accept_offer(Origin::signed(2), kitty_id, 2),
+                "You can't accept your own offer");
+        });
+    }
+
+    #[test]
+    fn frozen_kitties_do_not_count_toward_the_ownership_cap() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitties: Vec<H256> = (0..MaxKittiesOwned::get()).map(|_| create_kitty_for(1)).collect();
+            let gift = create_kitty_for(2);
+            assert_noop!(TemplateModule::transfer(Origin::signed(2), 1, gift, vec![]),
+                "The recipient owns too many kitties");
+
+            for kitty_id in &kitties {
+                TemplateModule::freeze_token(*kitty_id);
+            }
+            assert_eq!(TemplateModule::frozen_kitty_count(1), MaxKittiesOwned::get());
+
+            assert_ok!(TemplateModule::transfer(Origin::signed(2), 1, gift, vec![]));
+            create_kitty_for(1);
+            assert_eq!(TemplateModule::owned_kitty_count(1), MaxKittiesOwned::get() + 2);
+
+            TemplateModule::burn_token(kitties[0]);
+            assert_eq!(TemplateModule::frozen_kitty_count(1), MaxKittiesOwned::get() - 1);
         });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn offers_are_bounded_per_kitty() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create_kitty_for(1);
+            let other = create_kitty_for(1);
+            Balances::make_free_balance_be(&4, 10_000);
+
+            assert_ok!(TemplateModule::make_offer(Origin::signed(2), kitty_id, 300));
+            assert_ok!(TemplateModule::make_offer(Origin::signed(3), kitty_id, 300));
+            assert_noop!(TemplateModule::make_offer(Origin::signed(4), kitty_id, 300), "This cat has too many offers");
+            assert_ok!(TemplateModule::make_offer(Origin::signed(4), other, 300));
+
+            assert_ok!(TemplateModule::cancel_offer(Origin::signed(3), kitty_id));
+            assert_ok!(TemplateModule::make_offer(Origin::signed(4), kitty_id, 300));
+        });
+    }
+}
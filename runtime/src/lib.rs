@@ -32,8 +32,8 @@ use version::NativeVersion;
 pub use sr_primitives::BuildStorage;
 pub use timestamp::Call as TimestampCall;
 pub use balances::Call as BalancesCall;
-pub use sr_primitives::{Permill, Perbill};
-pub use support::{StorageValue, construct_runtime, parameter_types};
+pub use sr_primitives::{Permill, Perbill, Percent};
+pub use support::{StorageValue, construct_runtime, parameter_types, traits::Get};
 
 /// An index to a block.
 pub type BlockNumber = u32;
@@ -61,6 +61,9 @@ pub type Hash = primitives::H256;
 /// Digest item type.
 pub type DigestItem = generic::DigestItem<Hash>;
 
+/// A timestamp: milliseconds since the unix epoch.
+pub type Moment = u64;
+
 /// Used for the module template in `./template.rs`
 mod template;
 
@@ -103,8 +106,8 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("kitties-extension"),
 	impl_name: create_runtime_str!("kitties-extension"),
 	authoring_version: 3,
-	spec_version: 4,
-	impl_version: 4,
+	spec_version: 5,
+	impl_version: 5,
 	apis: RUNTIME_API_VERSIONS,
 };
 
@@ -219,7 +222,7 @@ parameter_types! {
 
 impl timestamp::Trait for Runtime {
 	/// A timestamp: milliseconds since the unix epoch.
-	type Moment = u64;
+	type Moment = Moment;
 	type OnTimestampSet = Babe;
 	type MinimumPeriod = MinimumPeriod;
 }
@@ -262,8 +265,121 @@ impl template::Trait for Runtime {
 	type Event = Event;
 }
 
+parameter_types! {
+	pub const MarketplaceFee: Percent = Percent::from_percent(5);
+	pub const ReportExpiring: bool = true;
+	pub const ExpiryWarningWindow: u64 = 60 * 60_000;
+	pub const DailyBreedLimit: u32 = 10;
+	pub const MaxMemoLength: u32 = 140;
+	pub const ExpiryBehavior: kitties::ExpiryMode = kitties::ExpiryMode::Burn;
+	pub const ExpiryEvents: kitties::ExpiryEvents = kitties::ExpiryEvents::Batch;
+	pub const MaxAccessories: u32 = 8;
+	pub const MinBalanceToCreate: Balance = 1_000;
+	pub const MaxNameLength: u32 = 32;
+	pub const TransferCooldown: u64 = 60 * 60_000;
+	pub const OfferTtl: u64 = 24 * 60 * 60_000;
+	pub const MaxOffersPerKitty: u32 = 50;
+	pub const MaxKittiesOwned: u64 = 100;
+	pub const ReaperBounty: Balance = 100;
+	pub const CreatorGating: bool = false;
+	pub const EmitDnaSummary: bool = false;
+	pub const ReferralPercent: Percent = Percent::from_percent(1);
+	pub const MinListingPrice: Balance = 1_000;
+	pub const SaleGracePeriod: u64 = 10 * 60_000;
+	pub const MaxOwnerHistory: u32 = 16;
+	pub const MaxFavorites: u32 = 100;
+	pub const MaxFavoritesPerKitty: u32 = 1_000;
+	pub const GenerationLifetimePercent: u32 = 95;
+	pub const MaxBatchPurchases: u32 = 10;
+	pub const SwapDeposit: Balance = 1_000;
+	pub const BreedFeeBase: Balance = 100;
+	pub const BreedFeeStep: Balance = 50;
+	pub const MaxYoungFactor: u8 = 60;
+	pub const MaxMaturityFactor: u8 = 30;
+	pub const MaxOldnessFactor: u8 = 60;
+	pub const ListingTtl: u64 = 7 * 24 * 60 * 60_000;
+}
+
+/// Rare DNA patterns, rarest first.
+pub struct FancyPatterns;
+
+impl Get<Vec<kitties::FancyPattern>> for FancyPatterns {
+	fn get() -> Vec<kitties::FancyPattern> {
+		vec![
+			kitties::FancyPattern { id: 1, prefix: vec![0x00, 0x00, 0x00] },
+			kitties::FancyPattern { id: 2, prefix: vec![0xff, 0xff] },
+		]
+	}
+}
+
+/// Account collecting marketplace fees. Reaper bounties are paid out of it.
+pub struct FeePot;
+
+impl Get<AccountId> for FeePot {
+	fn get() -> AccountId {
+		let mut raw = [0u8; 32];
+		raw[..15].copy_from_slice(b"kitties/fee-pot");
+		AccountId::from_raw(raw)
+	}
+}
+
+/// Routes reaper bounties to the current sudo key, which keeps the chain reaping.
+pub struct SudoKey;
+
+impl Get<AccountId> for SudoKey {
+	fn get() -> AccountId {
+		Sudo::key()
+	}
+}
+
 impl kitties::Trait for Runtime {
 	type Event = Event;
+	type Time = MTP;
+	type MarketplaceFee = MarketplaceFee;
+	type FeeDestination = FeePot;
+	type OnCreated = ();
+	type OnTransferred = ();
+	type ReportExpiring = ReportExpiring;
+	type ExpiryWarningWindow = ExpiryWarningWindow;
+	type DailyBreedLimit = DailyBreedLimit;
+	type MaxMemoLength = MaxMemoLength;
+	type ExpiryBehavior = ExpiryBehavior;
+	type ExpiryEvents = ExpiryEvents;
+	type MaxAccessories = MaxAccessories;
+	type MinBalanceToCreate = MinBalanceToCreate;
+	type MaxNameLength = MaxNameLength;
+	type FancyPatterns = FancyPatterns;
+	type TransferCooldown = TransferCooldown;
+	type OfferTtl = OfferTtl;
+	type MaxOffersPerKitty = MaxOffersPerKitty;
+	type MaxKittiesOwned = MaxKittiesOwned;
+	type ReaperBounty = ReaperBounty;
+	type ReaperRewardDestination = SudoKey;
+	type AdminOrigin = system::EnsureRoot<AccountId>;
+	type CreatorGating = CreatorGating;
+	type ReferralPercent = ReferralPercent;
+	type MinListingPrice = MinListingPrice;
+	type SaleGracePeriod = SaleGracePeriod;
+	type MaxOwnerHistory = MaxOwnerHistory;
+	type MaxFavorites = MaxFavorites;
+	type MaxFavoritesPerKitty = MaxFavoritesPerKitty;
+	type GenerationLifetimePercent = GenerationLifetimePercent;
+	type MaxBatchPurchases = MaxBatchPurchases;
+	type SwapDeposit = SwapDeposit;
+	type ExpiryDestination = ();
+	type BreedFeeBase = BreedFeeBase;
+	type BreedFeeStep = BreedFeeStep;
+	type BreedingWindow = ();
+	type MaxYoungFactor = MaxYoungFactor;
+	type MaxMaturityFactor = MaxMaturityFactor;
+	type MaxOldnessFactor = MaxOldnessFactor;
+	type ListingTtl = ListingTtl;
+	type DnaGenerator = ();
+	type Encumbrances = ();
+	type WeightInfo = ();
+	type EmitDnaSummary = EmitDnaSummary;
+	type AssetId = u32;
+	type Assets = ();
 }
 
 impl mtp::Trait for Runtime {
@@ -316,6 +432,31 @@ pub type CheckedExtrinsic = generic::CheckedExtrinsic<AccountId, Call, SignedExt
 /// Executive: handles dispatch to the various modules.
 pub type Executive = executive::Executive<Runtime, Block, system::ChainContext<Runtime>, Runtime, AllModules>;
 
+client::decl_runtime_apis! {
+	/// Read-only queries over the kitties module for frontends.
+	pub trait KittiesApi<Hash, Moment, Balance, AccountId> where
+		Hash: codec::Codec,
+		Moment: codec::Codec,
+		Balance: codec::Codec,
+		AccountId: codec::Codec,
+	{
+		/// Time left until the kitty can breed, `None` if it does not exist.
+		fn time_to_maturity(kitty_id: Hash) -> Option<Moment>;
+		/// Time left until the kitty dies, `None` if it does not exist.
+		fn time_to_death(kitty_id: Hash) -> Option<Moment>;
+		/// Lifetime the DNA would get if born at `birth`, `None` if it overflows.
+		fn simulate_lifetime(dna: Hash, birth: Moment) -> Option<kitties::Lifetime<Moment>>;
+		/// What the kitty can do right now, as `(can_breed, can_transfer, can_list)`.
+		fn capabilities(kitty_id: Hash) -> (bool, bool, bool);
+		/// Kitties for sale priced within `min..=max`. Scans every listing.
+		fn for_sale_in_range(min: Balance, max: Balance) -> Vec<Hash>;
+		/// Number of kitties listed for sale, kept as a counter so it is cheap to read.
+		fn for_sale_count() -> u64;
+		/// The kitty with its owner, life stage and name, `None` if it does not exist.
+		fn kitty_details(kitty_id: Hash) -> Option<kitties::KittyDetails<Hash, Balance, Moment, AccountId>>;
+	}
+}
+
 impl_runtime_apis! {
 	impl client_api::Core<Block> for Runtime {
 		fn version() -> RuntimeVersion {
@@ -415,6 +556,36 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl self::KittiesApi<Block, Hash, Moment, Balance, AccountId> for Runtime {
+		fn time_to_maturity(kitty_id: Hash) -> Option<Moment> {
+			Kitty::time_to_maturity(kitty_id)
+		}
+
+		fn time_to_death(kitty_id: Hash) -> Option<Moment> {
+			Kitty::time_to_death(kitty_id)
+		}
+
+		fn simulate_lifetime(dna: Hash, birth: Moment) -> Option<kitties::Lifetime<Moment>> {
+			Kitty::simulate_lifetime(dna, birth).ok()
+		}
+
+		fn capabilities(kitty_id: Hash) -> (bool, bool, bool) {
+			Kitty::capabilities(kitty_id)
+		}
+
+		fn for_sale_in_range(min: Balance, max: Balance) -> Vec<Hash> {
+			Kitty::for_sale_in_range(min, max)
+		}
+
+		fn for_sale_count() -> u64 {
+			Kitty::for_sale_count()
+		}
+
+		fn kitty_details(kitty_id: Hash) -> Option<kitties::KittyDetails<Hash, Balance, Moment, AccountId>> {
+			Kitty::kitty_details(kitty_id)
+		}
+	}
+
 	impl substrate_session::SessionKeys<Block> for Runtime {
 		fn generate_session_keys(seed: Option<Vec<u8>>) -> Vec<u8> {
 			let seed = seed.as_ref().map(|s| rstd::str::from_utf8(&s).expect("Seed is an utf8 string"));
@@ -1,15 +1,22 @@
 use rstd::prelude::*;
-use support::{decl_module, decl_storage, StorageValue};
+use sr_primitives::traits::{Saturating, Zero};
+use support::{decl_module, decl_storage, traits::Get, StorageValue};
 
 pub trait Trait: timestamp::Trait {}
 
+/// A source of median time past, so modules need not depend on this one directly.
+pub trait MedianTime {
+    type Moment;
+    fn median_time_past() -> Self::Moment;
+}
+
 const MAX_TIMESTAMP_SAMPLES: usize = 11;
 
 decl_storage! {
     trait Store for Module<T: Trait> as MTP {
         /// Stores the median time past calculated by the last 11 block.
         pub MedianTimePast get(median_time_past): T::Moment;
-        /// Stores the timestamps of last 11 blocks.
+        /// Stores the timestamps of last 11 blocks. Never longer than that, see `calculate_mtp`.
         pub SampleTimestamps get(sample_timestamps): Vec<T::Moment>;
     }
 }
@@ -18,17 +25,91 @@ decl_module! {
     pub struct Module<T: Trait> for enum Call where origin: T::Origin {
         fn on_finalize(_n: T::BlockNumber) {
             let now = <timestamp::Module<T>>::get();
+            if !Self::is_valid_timestamp(now) {
+                runtime_io::print("mtp: block timestamp is not after the median time past");
+            }
             Self::calculate_mtp(now);
         }
     }
 }
 
 impl<T: Trait> Module<T> {
+    /// Whether `t` is strictly after the median time past, the rule Bitcoin uses to
+    /// reject backdated block timestamps.
+    pub fn is_valid_timestamp(t: T::Moment) -> bool {
+        t > Self::median_time_past()
+    }
+
+    /// The sampled timestamp at `index`, oldest first, or `None` when out of range.
+    /// This still decodes the whole window, which is at most `MAX_TIMESTAMP_SAMPLES` long.
+    pub fn sample_at(index: usize) -> Option<T::Moment> {
+        <SampleTimestamps<T>>::get().get(index).cloned()
+    }
+
+    /// The `n` most recent samples, oldest first. The whole window is still decoded;
+    /// this only saves callers from slicing it themselves.
+    pub fn recent_samples(n: usize) -> Vec<T::Moment> {
+        let samples = <SampleTimestamps<T>>::get();
+        let skip = samples.len().saturating_sub(n);
+        samples[skip..].to_vec()
+    }
+
+    /// The latest sampled timestamp, `None` before the first block.
+    pub fn window_max() -> Option<T::Moment> {
+        <SampleTimestamps<T>>::get().into_iter().max()
+    }
+
+    /// The earliest sampled timestamp, `None` before the first block.
+    pub fn window_min() -> Option<T::Moment> {
+        <SampleTimestamps<T>>::get().into_iter().min()
+    }
+
+    /// Time between the earliest and latest sample. A large spread hints at
+    /// stalls or clock issues.
+    pub fn window_spread() -> T::Moment {
+        match (Self::window_max(), Self::window_min()) {
+            (Some(max), Some(min)) => max.saturating_sub(min),
+            _ => Zero::zero(),
+        }
+    }
+
+    /// Median gap between consecutive samples once sorted, a typical block time that
+    /// a single stalled or rushed block cannot skew. Zero with fewer than two samples.
+    pub fn median_block_time() -> T::Moment {
+        let mut samples = <SampleTimestamps<T>>::get();
+        if samples.len() < 2 {
+            return Zero::zero();
+        }
+        samples.sort();
+        let mut gaps: Vec<T::Moment> = samples.windows(2).map(|pair| pair[1].saturating_sub(pair[0])).collect();
+        gaps.sort();
+        gaps[gaps.len() / 2]
+    }
+
+    /// `time`, raised to `MinimumPeriod` past `previous` if it is closer than that.
+    /// A zero `MinimumPeriod` turns the guard off.
+    fn clamp_to_minimum_period(previous: T::Moment, time: T::Moment) -> T::Moment {
+        let min_period = T::MinimumPeriod::get();
+        let earliest = previous.saturating_add(min_period);
+        if !min_period.is_zero() && time < earliest {
+            runtime_io::print("mtp: timestamp is within the minimum period of the previous one, clamping");
+            earliest
+        } else {
+            time
+        }
+    }
+
     fn calculate_mtp(time: T::Moment) {
         let mut samples = <SampleTimestamps<T>>::get();
+        let time = match samples.last() {
+            Some(previous) => Self::clamp_to_minimum_period(*previous, time),
+            None => time,
+        };
         match samples.len() {
-            MAX_TIMESTAMP_SAMPLES => {
-                samples.remove(0);
+            // An overlong window is cut back to the newest samples, keeping the
+            // stored value, and so its decode cost, bounded.
+            len if len >= MAX_TIMESTAMP_SAMPLES => {
+                samples.drain(..len - MAX_TIMESTAMP_SAMPLES + 1);
                 samples.push(time);
             }
             _ => samples = [time; MAX_TIMESTAMP_SAMPLES].to_vec(),
@@ -40,6 +121,13 @@ impl<T: Trait> Module<T> {
     }
 }
 
+impl<T: Trait> MedianTime for Module<T> {
+    type Moment = T::Moment;
+    fn median_time_past() -> T::Moment {
+        <MedianTimePast<T>>::get()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -51,6 +139,7 @@ mod tests {
     use sr_primitives::weights::Weight;
     use sr_primitives::Perbill;
     use timestamp;
+    use std::cell::Cell;
 
     impl_outer_origin! {
 		pub enum Origin for Test {}
@@ -89,7 +178,19 @@ mod tests {
     impl timestamp::Trait for Test {
         type Moment = u64;
         type OnTimestampSet = ();
-        type MinimumPeriod = ();
+        type MinimumPeriod = TestMinimumPeriod;
+    }
+
+    thread_local! {
+        static MINIMUM_PERIOD: Cell<u64> = Cell::new(0);
+    }
+
+    pub struct TestMinimumPeriod;
+
+    impl Get<u64> for TestMinimumPeriod {
+        fn get() -> u64 {
+            MINIMUM_PERIOD.with(|period| period.get())
+        }
     }
 
     impl Trait for Test {
@@ -127,4 +228,120 @@ mod tests {
             assert_eq!(TemplateModule::sample_timestamps(), times.to_vec());
         });
     }
+
+    #[test]
+    fn is_valid_timestamp_test() {
+        with_externalities(&mut new_test_ext(), || {
+            TemplateModule::calculate_mtp(100);
+            assert!(!TemplateModule::is_valid_timestamp(99));
+            assert!(!TemplateModule::is_valid_timestamp(100));
+            assert!(TemplateModule::is_valid_timestamp(101));
+        });
+    }
+
+    #[test]
+    fn median_time_trait_test() {
+        with_externalities(&mut new_test_ext(), || {
+            TemplateModule::calculate_mtp(100);
+            assert_eq!(<TemplateModule as MedianTime>::median_time_past(), 100);
+        });
+    }
+
+    #[test]
+    fn sample_at_test() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(TemplateModule::sample_at(0), None);
+
+            TemplateModule::calculate_mtp(100);
+            TemplateModule::calculate_mtp(101);
+            assert_eq!(TemplateModule::sample_at(0), Some(100));
+            assert_eq!(TemplateModule::sample_at(MAX_TIMESTAMP_SAMPLES - 1), Some(101));
+            assert_eq!(TemplateModule::sample_at(MAX_TIMESTAMP_SAMPLES), None);
+        });
+    }
+
+    #[test]
+    fn window_spread_test() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(TemplateModule::window_spread(), 0);
+
+            TemplateModule::calculate_mtp(100);
+            for time in 101..=110 {
+                TemplateModule::calculate_mtp(time);
+            }
+            assert_eq!(TemplateModule::window_min(), Some(100));
+            assert_eq!(TemplateModule::window_max(), Some(110));
+            assert_eq!(TemplateModule::window_spread(), 10);
+
+            for time in &[200, 5_000, 150] {
+                TemplateModule::calculate_mtp(*time);
+            }
+            assert_eq!(TemplateModule::window_spread(), 5_000 - 103);
+        });
+    }
+
+    #[test]
+    fn minimum_period_test() {
+        with_externalities(&mut new_test_ext(), || {
+            MINIMUM_PERIOD.with(|period| period.set(5));
+            TemplateModule::calculate_mtp(100);
+
+            TemplateModule::calculate_mtp(110);
+            assert_eq!(TemplateModule::sample_at(MAX_TIMESTAMP_SAMPLES - 1), Some(110));
+
+            // Too close to the previous sample, and out of order.
+            TemplateModule::calculate_mtp(112);
+            assert_eq!(TemplateModule::sample_at(MAX_TIMESTAMP_SAMPLES - 1), Some(115));
+            TemplateModule::calculate_mtp(90);
+            assert_eq!(TemplateModule::sample_at(MAX_TIMESTAMP_SAMPLES - 1), Some(120));
+            assert_eq!(TemplateModule::window_max(), Some(120));
+        });
+    }
+
+    #[test]
+    fn recent_samples_test() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(TemplateModule::recent_samples(3), Vec::<u64>::new());
+
+            TemplateModule::calculate_mtp(100);
+            for time in 101..=105 {
+                TemplateModule::calculate_mtp(time);
+            }
+            assert_eq!(TemplateModule::recent_samples(3), vec![103, 104, 105]);
+            assert_eq!(TemplateModule::recent_samples(0), Vec::<u64>::new());
+            assert_eq!(TemplateModule::recent_samples(100), TemplateModule::sample_timestamps());
+        });
+    }
+
+    #[test]
+    fn median_block_time_test() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(TemplateModule::median_block_time(), 0);
+
+            TemplateModule::calculate_mtp(100);
+            assert_eq!(TemplateModule::median_block_time(), 0);
+
+            // One short gap and one long stall around otherwise 10 second blocks.
+            for time in &[110, 120, 130, 135, 200, 210, 220, 230, 240, 250] {
+                TemplateModule::calculate_mtp(*time);
+            }
+            assert_eq!(TemplateModule::median_block_time(), 10);
+        });
+    }
+
+    #[test]
+    fn overlong_window_test() {
+        with_externalities(&mut new_test_ext(), || {
+            <SampleTimestamps<Test>>::put((100..120).collect::<Vec<u64>>());
+
+            TemplateModule::calculate_mtp(120);
+            let expected: Vec<u64> = (110..=120).collect();
+            assert_eq!(TemplateModule::sample_timestamps(), expected);
+            assert_eq!(TemplateModule::median_time_past(), 115);
+
+            TemplateModule::calculate_mtp(121);
+            assert_eq!(TemplateModule::sample_timestamps().len(), MAX_TIMESTAMP_SAMPLES);
+            assert_eq!(TemplateModule::sample_at(0), Some(111));
+        });
+    }
 }